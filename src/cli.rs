@@ -25,7 +25,7 @@ pub struct Cli {
 #[derive(Debug, Parser)]
 pub enum SubCommand {
     #[command(name = "csv", about = "Show CSV or convert CSV to other formats")]
-    Csv(CsvOpts),
+    Csv(Box<CsvOpts>),
     #[command(name = "genpass", about = "Generate a random password")]
     GenPass(GenPassOpts),
     #[command(name = "base64", about = "Base64 encode/decode")]
@@ -44,7 +44,7 @@ pub enum SubCommand {
 impl CmdExecutor for SubCommand {
     async fn execute(self) -> anyhow::Result<()> {
         match self {
-            SubCommand::Csv(opts) => opts.execute().await,
+            SubCommand::Csv(opts) => (*opts).execute().await,
             SubCommand::GenPass(opts) => opts.execute().await,
             SubCommand::Base64(cmd) => cmd.execute().await,
             SubCommand::Text(cmd) => cmd.execute().await,
@@ -73,7 +73,15 @@ fn verify_path(path: &str) -> Result<PathBuf, &'static str> {
 
 #[cfg(test)]
 mod tests {
-    use super::verify_file;
+    use super::{verify_file, Cli};
+    use clap::CommandFactory;
+
+    // 检查各个子命令之间是否有重名的参数
+    #[test]
+    fn test_cli_definition() {
+        Cli::command().debug_assert();
+    }
+
     #[test]
     fn test_verify_input_file() {
         assert_eq!(verify_file("-"), Ok("-".into()));
@@ -1,19 +1,161 @@
-use crate::{process_csv, CmdExecutor};
+use crate::{
+    process_csv, process_csv_cat, process_csv_decrypt, process_csv_dedupe, process_csv_diff,
+    process_csv_encrypt, process_csv_from, process_csv_join, process_csv_mask, process_csv_query,
+    process_csv_sample, process_csv_show, process_csv_sort, process_csv_split, process_csv_stats,
+    process_csv_validate, CmdExecutor,
+};
 
 use super::verify_file;
-use clap::Parser;
+use clap::{ArgAction, Args, Parser};
+use encoding_rs::Encoding;
+use enum_dispatch::enum_dispatch;
 use std::{fmt, str::FromStr};
 
 #[derive(Debug, Clone, Copy)]
 pub enum OutputFormat {
+    Csv,
     Json,
+    Ndjson,
     Yaml,
     Toml,
+    Markdown,
+    Html,
+    Xml,
+    Sqlite,
+    Sql,
+    Parquet,
+    Arrow,
 }
 
+#[derive(Debug, Clone, Copy)]
+pub enum CsvTrim {
+    None,
+    Headers,
+    Fields,
+    All,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CsvType {
+    String,
+    Integer,
+    Float,
+    Boolean,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SqlDialect {
+    Sqlite,
+    Postgres,
+    Mysql,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MaskStrategy {
+    Hash,
+    Redact,
+    Partial,
+    Fake,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Compression {
+    None,
+    Snappy,
+    Zstd,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OnError {
+    Skip,
+    Fail,
+    Quarantine,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CsvJoinType {
+    Inner,
+    Left,
+    Right,
+    Full,
+}
+
+// `--by col[:desc][:num]` 解析后的排序键
+#[derive(Debug, Clone, PartialEq)]
+pub struct CsvSortKey {
+    pub column: String,
+    pub desc: bool,
+    pub numeric: bool,
+}
+
+// `--range B3:F20` 解析后的单元格区域, 行列都从 0 开始; 没有结束位置时读到表格末尾
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CellRange {
+    pub start: (u32, u32),
+    pub end: Option<(u32, u32)>,
+}
+
+// 不带子命令时保持 `rcli csv -i xxx.csv` 的转换行为
 #[derive(Debug, Parser)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 pub struct CsvOpts {
-    #[arg(short, long, value_parser = verify_file)]
+    #[command(subcommand)]
+    pub cmd: Option<CsvSubCommand>,
+
+    #[command(flatten)]
+    pub convert: CsvConvertOpts,
+}
+
+#[enum_dispatch(CmdExecutor)]
+#[derive(Debug, Parser)]
+pub enum CsvSubCommand {
+    #[command(name = "from", about = "Convert JSON/NDJSON/YAML/TOML back to CSV")]
+    From(CsvFromOpts),
+    #[command(name = "show", about = "Show CSV as a table in the terminal")]
+    Show(CsvShowOpts),
+    #[command(name = "stats", about = "Show per-column statistics of CSV")]
+    Stats(CsvStatsOpts),
+    #[command(name = "sort", about = "Sort CSV rows by columns")]
+    Sort(CsvSortOpts),
+    #[command(name = "dedupe", about = "Remove duplicate CSV rows")]
+    Dedupe(CsvDedupeOpts),
+    #[command(name = "sample", about = "Randomly sample CSV rows")]
+    Sample(CsvSampleOpts),
+    #[command(name = "join", about = "Join two CSV files on key columns")]
+    Join(CsvJoinOpts),
+    #[command(
+        name = "diff",
+        about = "Show added, removed and modified rows between two CSV files"
+    )]
+    Diff(CsvDiffOpts),
+    #[command(name = "query", about = "Run SQL over CSV files")]
+    Query(CsvQueryOpts),
+    #[command(name = "validate", about = "Validate CSV rows against a YAML schema")]
+    Validate(CsvValidateOpts),
+    #[command(name = "mask", about = "Pseudonymize or mask PII columns in CSV")]
+    Mask(CsvMaskOpts),
+    #[command(name = "encrypt", about = "Encrypt CSV columns with ChaCha20-Poly1305")]
+    Encrypt(CsvEncryptOpts),
+    #[command(
+        name = "decrypt",
+        about = "Decrypt CSV columns encrypted by `csv encrypt`"
+    )]
+    Decrypt(CsvDecryptOpts),
+    #[command(
+        name = "split",
+        about = "Split CSV into files by row count or column value"
+    )]
+    Split(CsvSplitOpts),
+    #[command(
+        name = "cat",
+        about = "Concatenate CSV files, aligning columns by header"
+    )]
+    Cat(CsvCatOpts),
+}
+
+#[derive(Debug, Args)]
+pub struct CsvConvertOpts {
+    #[arg(short, long, value_parser = verify_file, default_value = "-")]
     pub input: String,
 
     #[arg(short, long)]
@@ -22,11 +164,537 @@ pub struct CsvOpts {
     #[arg(long, default_value = "json", value_parser = parse_format)]
     pub format: OutputFormat,
 
-    #[arg(short, long, default_value_t = ',')]
-    delimiter: char,
+    #[command(flatten)]
+    pub reader: CsvReaderOpts,
+
+    #[command(flatten)]
+    pub types: CsvTypeOpts,
+
+    #[command(flatten)]
+    pub filter: CsvFilterOpts,
+
+    #[command(flatten)]
+    pub output_opts: CsvOutputOpts,
+
+    #[command(flatten)]
+    pub errors: CsvErrorOpts,
+}
+
+#[derive(Debug, Parser)]
+pub struct CsvShowOpts {
+    #[arg(short, long, value_parser = verify_file, default_value = "-")]
+    pub input: String,
+
+    #[command(flatten)]
+    pub reader: CsvReaderOpts,
+
+    #[command(flatten)]
+    pub filter: CsvFilterOpts,
+
+    #[command(flatten)]
+    pub table: CsvTableOpts,
+}
+
+#[derive(Debug, Parser)]
+pub struct CsvStatsOpts {
+    #[arg(short, long, value_parser = verify_file, default_value = "-")]
+    pub input: String,
+
+    #[arg(short, long, default_value = "-")]
+    pub output: String,
+
+    // 不指定时输出表格; parquet/arrow 中数字列和文本列的 min/max/median 混在一起, 写成文本
+    #[arg(long, value_parser = parse_format)]
+    pub format: Option<OutputFormat>,
+
+    // 每列显示出现次数最多的 N 个值
+    #[arg(long, default_value_t = 5)]
+    pub top: usize,
+
+    // 使用 HyperLogLog 估算去重数, 适合很大的文件
+    #[arg(long, default_value_t = false)]
+    pub approx: bool,
+
+    #[command(flatten)]
+    pub reader: CsvReaderOpts,
+
+    #[command(flatten)]
+    pub filter: CsvFilterOpts,
+}
+
+#[derive(Debug, Parser)]
+pub struct CsvFromOpts {
+    #[arg(short, long, value_parser = verify_file, default_value = "-")]
+    pub input: String,
+
+    #[arg(short, long, default_value = "output.csv")]
+    pub output: String,
+
+    // 不指定时根据输入文件的扩展名判断
+    #[arg(long, value_parser = parse_format)]
+    pub format: Option<OutputFormat>,
+
+    #[arg(short, long, default_value = ",", value_parser = parse_ascii)]
+    pub delimiter: u8,
+}
+
+#[derive(Debug, Parser)]
+pub struct CsvSortOpts {
+    #[arg(short, long, value_parser = verify_file, default_value = "-")]
+    pub input: String,
+
+    #[arg(short, long, default_value = "-")]
+    pub output: String,
+
+    // 例如 `--by Nationality --by "Kit Number:desc:num"`, 靠前的键优先
+    #[arg(long, required = true, value_delimiter = ',', value_parser = parse_sort_key)]
+    pub by: Vec<CsvSortKey>,
+
+    // 内存预算 (MB), 超出后分块排序写入临时文件再归并
+    #[arg(long, default_value_t = 512)]
+    pub memory: usize,
+
+    #[command(flatten)]
+    pub reader: CsvReaderOpts,
+
+    #[command(flatten)]
+    pub filter: CsvFilterOpts,
+}
+
+#[derive(Debug, Parser)]
+pub struct CsvDedupeOpts {
+    #[arg(short, long, value_parser = verify_file, default_value = "-")]
+    pub input: String,
+
+    #[arg(short, long, default_value = "-")]
+    pub output: String,
+
+    // 不指定时比较整行, 保留第一次出现的行
+    #[arg(long, value_delimiter = ',')]
+    pub key: Vec<String>,
+
+    #[command(flatten)]
+    pub reader: CsvReaderOpts,
 
-    #[arg(long, default_value_t = true)]
-    header: bool,
+    #[command(flatten)]
+    pub filter: CsvFilterOpts,
+}
+
+#[derive(Debug, Parser)]
+pub struct CsvSampleOpts {
+    #[arg(short, long, value_parser = verify_file, default_value = "-")]
+    pub input: String,
+
+    #[arg(short, long, default_value = "-")]
+    pub output: String,
+
+    #[arg(short = 'n', long, default_value_t = 10)]
+    pub size: usize,
+
+    // 指定后每次抽样结果相同
+    #[arg(long)]
+    pub seed: Option<u64>,
+
+    #[command(flatten)]
+    pub reader: CsvReaderOpts,
+
+    #[command(flatten)]
+    pub filter: CsvFilterOpts,
+}
+
+#[derive(Debug, Parser)]
+pub struct CsvJoinOpts {
+    #[arg(value_parser = verify_file)]
+    pub left: String,
+
+    #[arg(value_parser = verify_file)]
+    pub right: String,
+
+    // 两侧列名相同时写 `--on id`, 不同时写 `--on id=player_id`
+    #[arg(long, required = true, value_delimiter = ',', value_parser = parse_join_key)]
+    pub on: Vec<(String, String)>,
+
+    // 输出按左侧文件的顺序, 只在右侧出现的行排在最后
+    #[arg(long, default_value = "inner", value_parser = parse_join_type)]
+    pub how: CsvJoinType,
+
+    #[arg(short, long, default_value = "-")]
+    pub output: String,
+
+    // 不指定时输出 csv
+    #[arg(long, value_parser = parse_format)]
+    pub format: Option<OutputFormat>,
+
+    // 两侧都有的非键列分别加上这两个后缀
+    #[arg(long, default_value = "_left")]
+    pub left_suffix: String,
+
+    #[arg(long, default_value = "_right")]
+    pub right_suffix: String,
+
+    #[command(flatten)]
+    pub reader: CsvReaderOpts,
+
+    #[command(flatten)]
+    pub types: CsvTypeOpts,
+
+    #[command(flatten)]
+    pub output_opts: CsvOutputOpts,
+}
+
+#[derive(Debug, Parser)]
+pub struct CsvDiffOpts {
+    #[arg(value_parser = verify_file)]
+    pub old: String,
+
+    #[arg(value_parser = verify_file)]
+    pub new: String,
+
+    // 用来匹配新旧两行的列, 在每个文件中必须唯一
+    #[arg(long, required = true, value_delimiter = ',')]
+    pub key: Vec<String>,
+
+    #[arg(short, long, default_value = "-")]
+    pub output: String,
+
+    // 不指定时输出便于阅读的文本, 支持 json 和 yaml
+    #[arg(long, value_parser = parse_format)]
+    pub format: Option<OutputFormat>,
+
+    #[command(flatten)]
+    pub reader: CsvReaderOpts,
+}
+
+#[derive(Debug, Parser)]
+pub struct CsvQueryOpts {
+    // 例如 "SELECT Position, count(*) FROM players GROUP BY Position"
+    pub sql: String,
+
+    // 形如 `--table players=assets/juventus.csv`, 只写路径时用文件名作为表名
+    #[arg(long, required = true, value_parser = parse_query_table)]
+    pub table: Vec<(String, String)>,
+
+    #[arg(short, long, default_value = "-")]
+    pub output: String,
+
+    // 不指定时输出表格
+    #[arg(long, value_parser = parse_format)]
+    pub format: Option<OutputFormat>,
+
+    #[command(flatten)]
+    pub reader: CsvReaderOpts,
+}
+
+#[derive(Debug, Parser)]
+pub struct CsvValidateOpts {
+    #[arg(short, long, value_parser = verify_file, default_value = "-")]
+    pub input: String,
+
+    // 格式见 assets/juventus.schema.yaml
+    #[arg(long, value_parser = verify_file)]
+    pub schema: String,
+
+    #[arg(short, long, default_value = "-")]
+    pub output: String,
+
+    // 不指定时每个错误输出一行文本
+    #[arg(long, value_parser = parse_format)]
+    pub format: Option<OutputFormat>,
+
+    #[command(flatten)]
+    pub reader: CsvReaderOpts,
+}
+
+#[derive(Debug, Parser)]
+pub struct CsvMaskOpts {
+    #[arg(short, long, value_parser = verify_file, default_value = "-")]
+    pub input: String,
+
+    #[arg(short, long, default_value = "-")]
+    pub output: String,
+
+    // 形如 `--column Name:hash,DOB:redact`, 支持 hash/redact/partial/fake
+    #[arg(long, required = true, value_delimiter = ',', value_parser = parse_mask_rule)]
+    pub column: Vec<(String, MaskStrategy)>,
+
+    // blake3 的密钥文件, 可以用 `rcli text generate` 生成; hash 必须指定, fake 指定后结果可复现
+    #[arg(long, value_parser = verify_file)]
+    pub key: Option<String>,
+
+    #[command(flatten)]
+    pub reader: CsvReaderOpts,
+}
+
+#[derive(Debug, Parser)]
+pub struct CsvEncryptOpts {
+    #[command(flatten)]
+    pub crypt: CsvCryptOpts,
+}
+
+#[derive(Debug, Parser)]
+pub struct CsvDecryptOpts {
+    #[command(flatten)]
+    pub crypt: CsvCryptOpts,
+}
+
+// encrypt 和 decrypt 共用的参数
+#[derive(Debug, Args)]
+pub struct CsvCryptOpts {
+    #[arg(short, long, value_parser = verify_file, default_value = "-")]
+    pub input: String,
+
+    #[arg(short, long, default_value = "-")]
+    pub output: String,
+
+    // 密文与列名绑定, 改了列名或者移到其他列后无法解密; 同一列中不同行的密文互换不会被发现
+    #[arg(long, required = true, value_delimiter = ',')]
+    pub column: Vec<String>,
+
+    // 取文件的前 32 个字节作为密钥, 可以用 `rcli text generate` 生成
+    #[arg(long, value_parser = verify_file)]
+    pub key: String,
+
+    #[command(flatten)]
+    pub reader: CsvReaderOpts,
+}
+
+#[derive(Debug, Parser)]
+pub struct CsvSplitOpts {
+    #[arg(short, long, value_parser = verify_file, default_value = "-")]
+    pub input: String,
+
+    // 拆分出的文件写到这个目录, 不存在时自动创建
+    #[arg(short, long, default_value = ".")]
+    pub output_dir: String,
+
+    // 文件名前缀, 默认用输入的文件名, 例如 juventus_0001.csv
+    #[arg(long)]
+    pub prefix: Option<String>,
+
+    // 每个文件最多的行数, 不含表头
+    #[arg(
+        long,
+        conflicts_with = "by_column",
+        required_unless_present = "by_column"
+    )]
+    pub rows: Option<usize>,
+
+    // 每个不同的取值一个文件, 例如 juventus_Italy.csv
+    #[arg(long)]
+    pub by_column: Option<String>,
+
+    #[command(flatten)]
+    pub reader: CsvReaderOpts,
+}
+
+#[derive(Debug, Parser)]
+pub struct CsvCatOpts {
+    #[arg(required = true, value_parser = verify_file)]
+    pub inputs: Vec<String>,
+
+    #[arg(short, long, default_value = "-")]
+    pub output: String,
+
+    #[command(flatten)]
+    pub reader: CsvReaderOpts,
+}
+
+// 读取 csv 时的方言配置, 其他 csv 相关的子命令也可以 flatten 复用
+#[derive(Debug, Clone, Args)]
+pub struct CsvReaderOpts {
+    #[arg(short, long, default_value = ",", value_parser = parse_ascii)]
+    pub delimiter: u8,
+
+    // bool 默认是 SetTrue, 这里需要 `--header false` 才能关闭表头
+    #[arg(long, default_value_t = true, action = ArgAction::Set)]
+    pub header: bool,
+
+    #[arg(long, default_value = "\"", value_parser = parse_ascii)]
+    pub quote: u8,
+
+    #[arg(long, value_parser = parse_ascii)]
+    pub escape: Option<u8>,
+
+    #[arg(long, value_parser = parse_ascii)]
+    pub comment: Option<u8>,
+
+    #[arg(long, default_value_t = false)]
+    pub flexible: bool,
+
+    #[arg(long, default_value = "none", value_parser = parse_trim)]
+    pub trim: CsvTrim,
+
+    // 例如 utf-16le, windows-1252; 不指定时根据 BOM 和内容自动检测
+    #[arg(long, value_parser = parse_encoding)]
+    pub encoding: Option<&'static Encoding>,
+
+    // 根据输入的前 64KB 自动检测分隔符/引号/表头, 会覆盖上面的对应配置
+    #[arg(long, default_value_t = false)]
+    pub sniff: bool,
+
+    // 把检测到的方言等诊断信息打印到 stderr
+    #[arg(short, long, default_value_t = false)]
+    pub verbose: bool,
+
+    // 读取 xlsx/xls/ods 时的工作表, 可以是名称或从 1 开始的序号, 默认第一个
+    #[arg(long)]
+    pub sheet: Option<String>,
+
+    // 读取 xlsx/xls/ods 时的单元格区域, 例如 B3:F20 或 B3
+    #[arg(long, value_parser = parse_cell_range)]
+    pub range: Option<CellRange>,
+
+    // 按列定义读取定宽文本, 格式见 assets/accounts.fixed.yaml
+    #[arg(long, value_parser = verify_file)]
+    pub fixed_width: Option<String>,
+}
+
+impl Default for CsvReaderOpts {
+    fn default() -> Self {
+        Self {
+            delimiter: b',',
+            header: true,
+            quote: b'"',
+            escape: None,
+            comment: None,
+            flexible: false,
+            trim: CsvTrim::None,
+            encoding: None,
+            sniff: false,
+            verbose: false,
+            sheet: None,
+            range: None,
+            fixed_width: None,
+        }
+    }
+}
+
+// 把单元格从字符串转换成 JSON/YAML/TOML 的原生类型
+#[derive(Debug, Clone, Default, Args)]
+pub struct CsvTypeOpts {
+    #[arg(long, default_value_t = false)]
+    pub infer: bool,
+
+    // 形如 `--types "Kit Number:integer,DOB:string"`, 优先级高于 --infer
+    #[arg(long, value_delimiter = ',', value_parser = parse_column_type)]
+    pub types: Vec<(String, CsvType)>,
+}
+
+#[derive(Debug, Clone, Default, Args)]
+pub struct CsvFilterOpts {
+    // 只保留这些列, 按给定的顺序输出
+    #[arg(long, value_delimiter = ',')]
+    pub select: Vec<String>,
+
+    // 例如 `--where "Nationality == 'Italy' && Kit Number > 10"`
+    #[arg(long = "where")]
+    pub filter: Option<String>,
+}
+
+// 遇到无法解析或无法转换类型的行时的处理方式
+#[derive(Debug, Clone, Args)]
+pub struct CsvErrorOpts {
+    // fail 时立即退出, skip 丢弃坏行, quarantine 把坏行写到 --quarantine 指定的文件
+    #[arg(long, default_value = "fail", value_parser = parse_on_error)]
+    pub on_error: OnError,
+
+    // 每行记录坏行的行号、字节偏移和原因, 后面是 --select 之前的各个字段.
+    // 输入经过解压、转码或定宽转换时, 位置是转换后的 csv 中的位置, 不是原文件中的位置
+    #[arg(
+        long,
+        required_if_eq("on_error", "quarantine"),
+        help = "Write rejected rows here as line, byte, reason, then the row's fields decoded \
+                to UTF-8; line and byte are positions in the decoded CSV stream, which differ \
+                from the input file when it is compressed, transcoded or fixed-width"
+    )]
+    pub quarantine: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Args)]
+pub struct CsvTableOpts {
+    // 只显示前 N 行
+    #[arg(long)]
+    pub head: Option<usize>,
+
+    // 只显示后 N 行, 与 --head 同时使用时中间用 … 省略
+    #[arg(long)]
+    pub tail: Option<usize>,
+
+    // 默认使用终端宽度
+    #[arg(long)]
+    pub width: Option<usize>,
+
+    // 过长的单元格换行显示, 默认截断
+    #[arg(long, default_value_t = false)]
+    pub wrap: bool,
+}
+
+// markdown/html/xml 等输出格式的额外配置
+#[derive(Debug, Clone, Args)]
+pub struct CsvOutputOpts {
+    // html 输出完整的页面, 而不只是 <table>
+    #[arg(long, default_value_t = false)]
+    pub standalone: bool,
+
+    #[arg(long, default_value = "rows")]
+    pub xml_root: String,
+
+    #[arg(long, default_value = "row")]
+    pub xml_row: String,
+
+    // 指定后字段写成 <field name="列名">, 否则直接用列名作为元素名
+    #[arg(long)]
+    pub xml_field: Option<String>,
+
+    // 把 address.city, tags[0] 这样的表头还原成嵌套的对象和数组
+    #[arg(long, default_value_t = false)]
+    pub unflatten: bool,
+
+    // sqlite/sql 输出的表名, 默认使用输入文件名
+    #[arg(long)]
+    pub table: Option<String>,
+
+    // sql 脚本的方言
+    #[arg(long, default_value = "sqlite", value_parser = parse_sql_dialect)]
+    pub sql_dialect: SqlDialect,
+
+    // 每个事务插入的行数
+    #[arg(long, default_value_t = 1000)]
+    pub batch_size: usize,
+
+    // parquet 默认 snappy, arrow 默认不压缩 (arrow 不支持 snappy)
+    #[arg(long, value_parser = parse_compression)]
+    pub compression: Option<Compression>,
+
+    // parquet 每个 row group 的行数, 也是 arrow 每个 record batch 的行数
+    #[arg(long, default_value_t = 65536)]
+    pub row_group_size: usize,
+}
+
+impl Default for CsvOutputOpts {
+    fn default() -> Self {
+        Self {
+            standalone: false,
+            xml_root: "rows".into(),
+            xml_row: "row".into(),
+            xml_field: None,
+            unflatten: false,
+            table: None,
+            sql_dialect: SqlDialect::Sqlite,
+            batch_size: 1000,
+            compression: None,
+            row_group_size: 65536,
+        }
+    }
+}
+
+impl Default for CsvErrorOpts {
+    fn default() -> Self {
+        Self {
+            on_error: OnError::Fail,
+            quarantine: None,
+        }
+    }
 }
 
 fn parse_format(format: &str) -> Result<OutputFormat, anyhow::Error> {
@@ -34,13 +702,158 @@ fn parse_format(format: &str) -> Result<OutputFormat, anyhow::Error> {
     format.parse()
 }
 
+fn parse_sql_dialect(dialect: &str) -> Result<SqlDialect, anyhow::Error> {
+    dialect.parse()
+}
+
+fn parse_compression(compression: &str) -> Result<Compression, anyhow::Error> {
+    compression.parse()
+}
+
+fn parse_trim(trim: &str) -> Result<CsvTrim, anyhow::Error> {
+    trim.parse()
+}
+
+fn parse_encoding(label: &str) -> Result<&'static Encoding, anyhow::Error> {
+    Encoding::for_label(label.as_bytes())
+        .ok_or_else(|| anyhow::anyhow!("Unsupported encoding: {}", label))
+}
+
+fn parse_column_type(s: &str) -> Result<(String, CsvType), anyhow::Error> {
+    let (name, ty) = s
+        .rsplit_once(':')
+        .ok_or_else(|| anyhow::anyhow!("Expected <column>:<type>, got: {}", s))?;
+    Ok((name.to_string(), ty.parse()?))
+}
+
+fn parse_on_error(on_error: &str) -> Result<OnError, anyhow::Error> {
+    on_error.parse()
+}
+
+fn parse_join_type(how: &str) -> Result<CsvJoinType, anyhow::Error> {
+    how.parse()
+}
+
+fn parse_query_table(s: &str) -> Result<(String, String), anyhow::Error> {
+    let (name, path) = match s.split_once('=') {
+        Some((name, path)) => (name.to_string(), path),
+        None => {
+            let stem = std::path::Path::new(s)
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .ok_or_else(|| anyhow::anyhow!("Cannot derive table name from {}", s))?;
+            (stem.split('.').next().unwrap_or(stem).to_string(), s)
+        }
+    };
+    Ok((name, verify_file(path).map_err(anyhow::Error::msg)?))
+}
+
+fn parse_mask_rule(s: &str) -> Result<(String, MaskStrategy), anyhow::Error> {
+    let (name, strategy) = s
+        .rsplit_once(':')
+        .ok_or_else(|| anyhow::anyhow!("Expected <column>:<strategy>, got: {}", s))?;
+    Ok((name.to_string(), strategy.parse()?))
+}
+
+fn parse_join_key(s: &str) -> Result<(String, String), anyhow::Error> {
+    let (left, right) = s.split_once('=').unwrap_or((s, s));
+    if left.is_empty() || right.is_empty() {
+        return Err(anyhow::anyhow!(
+            "Expected <column> or <left>=<right>, got: {}",
+            s
+        ));
+    }
+    Ok((left.to_string(), right.to_string()))
+}
+
+// 从右边解析修饰符, 列名本身可以包含 ':'
+fn parse_sort_key(s: &str) -> Result<CsvSortKey, anyhow::Error> {
+    let mut key = CsvSortKey {
+        column: s.to_string(),
+        desc: false,
+        numeric: false,
+    };
+    while let Some((column, modifier)) = key.column.rsplit_once(':') {
+        match modifier.to_lowercase().as_str() {
+            "desc" => key.desc = true,
+            "asc" => key.desc = false,
+            "num" | "numeric" => key.numeric = true,
+            _ => break,
+        }
+        key.column = column.to_string();
+    }
+    if key.column.is_empty() {
+        return Err(anyhow::anyhow!("Missing column in sort key: {}", s));
+    }
+    Ok(key)
+}
+
+fn parse_cell_range(s: &str) -> Result<CellRange, anyhow::Error> {
+    let (start, end) = match s.split_once(':') {
+        Some((start, "")) => (start, None),
+        Some((start, end)) => (start, Some(end)),
+        None => (s, None),
+    };
+    let range = CellRange {
+        start: parse_cell(start)?,
+        end: end.map(parse_cell).transpose()?,
+    };
+    if let Some(end) = range.end {
+        if end.0 < range.start.0 || end.1 < range.start.1 {
+            return Err(anyhow::anyhow!("Invalid cell range: {}", s));
+        }
+    }
+    Ok(range)
+}
+
+// A1 => (0, 0), AB12 => (11, 27)
+fn parse_cell(s: &str) -> Result<(u32, u32), anyhow::Error> {
+    let invalid = || anyhow::anyhow!("Invalid cell reference: {}", s);
+    let split = s
+        .find(|c: char| !c.is_ascii_alphabetic())
+        .ok_or_else(invalid)?;
+    let (letters, digits) = s.split_at(split);
+    if letters.is_empty() || letters.len() > 3 {
+        return Err(invalid());
+    }
+    let col = letters.bytes().fold(0, |acc, b| {
+        acc * 26 + (b.to_ascii_uppercase() - b'A') as u32 + 1
+    });
+    let row: u32 = digits.parse().map_err(|_| invalid())?;
+    if row == 0 {
+        return Err(invalid());
+    }
+    Ok((row - 1, col - 1))
+}
+
+// csv crate 只接受单字节的分隔符/引号等配置
+fn parse_ascii(s: &str) -> Result<u8, anyhow::Error> {
+    let s = match s {
+        "\\t" | "tab" => "\t",
+        s => s,
+    };
+    match s.as_bytes() {
+        [b] if b.is_ascii() => Ok(*b),
+        _ => Err(anyhow::anyhow!("Expected a single ASCII character: {}", s)),
+    }
+}
+
 // 实现 Display trait 时候需要用到
 impl From<OutputFormat> for &'static str {
     fn from(format: OutputFormat) -> Self {
         match format {
+            OutputFormat::Csv => "csv",
             OutputFormat::Json => "json",
+            OutputFormat::Ndjson => "ndjson",
             OutputFormat::Toml => "toml",
             OutputFormat::Yaml => "yaml",
+            OutputFormat::Markdown => "md",
+            OutputFormat::Html => "html",
+            OutputFormat::Xml => "xml",
+            OutputFormat::Sqlite => "sqlite",
+            OutputFormat::Sql => "sql",
+            OutputFormat::Parquet => "parquet",
+            OutputFormat::Arrow => "arrow",
         }
     }
 }
@@ -51,9 +864,18 @@ impl FromStr for OutputFormat {
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s.to_lowercase().as_str() {
+            "csv" => Ok(OutputFormat::Csv),
             "json" => Ok(OutputFormat::Json),
-            "yaml" => Ok(OutputFormat::Yaml),
+            "ndjson" | "jsonl" => Ok(OutputFormat::Ndjson),
+            "yaml" | "yml" => Ok(OutputFormat::Yaml),
             "toml" => Ok(OutputFormat::Toml),
+            "markdown" | "md" => Ok(OutputFormat::Markdown),
+            "html" | "htm" => Ok(OutputFormat::Html),
+            "xml" => Ok(OutputFormat::Xml),
+            "sqlite" | "sqlite3" | "db" => Ok(OutputFormat::Sqlite),
+            "sql" => Ok(OutputFormat::Sql),
+            "parquet" | "pq" => Ok(OutputFormat::Parquet),
+            "arrow" | "ipc" | "feather" => Ok(OutputFormat::Arrow),
             v => Err(anyhow::anyhow!("Unsupported format: {}", v)),
         }
     }
@@ -65,13 +887,503 @@ impl fmt::Display for OutputFormat {
     }
 }
 
+impl FromStr for CsvTrim {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "none" => Ok(CsvTrim::None),
+            "headers" => Ok(CsvTrim::Headers),
+            "fields" => Ok(CsvTrim::Fields),
+            "all" => Ok(CsvTrim::All),
+            v => Err(anyhow::anyhow!("Unsupported trim: {}", v)),
+        }
+    }
+}
+
+impl From<CsvTrim> for &'static str {
+    fn from(trim: CsvTrim) -> Self {
+        match trim {
+            CsvTrim::None => "none",
+            CsvTrim::Headers => "headers",
+            CsvTrim::Fields => "fields",
+            CsvTrim::All => "all",
+        }
+    }
+}
+
+impl fmt::Display for CsvTrim {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
+
+impl FromStr for CsvType {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "string" | "str" => Ok(CsvType::String),
+            "integer" | "int" => Ok(CsvType::Integer),
+            "float" => Ok(CsvType::Float),
+            "boolean" | "bool" => Ok(CsvType::Boolean),
+            v => Err(anyhow::anyhow!("Unsupported type: {}", v)),
+        }
+    }
+}
+
+impl From<CsvType> for &'static str {
+    fn from(ty: CsvType) -> Self {
+        match ty {
+            CsvType::String => "string",
+            CsvType::Integer => "integer",
+            CsvType::Float => "float",
+            CsvType::Boolean => "boolean",
+        }
+    }
+}
+
+impl fmt::Display for CsvType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
+
+impl FromStr for SqlDialect {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "sqlite" | "sqlite3" => Ok(SqlDialect::Sqlite),
+            "postgres" | "postgresql" | "pg" => Ok(SqlDialect::Postgres),
+            "mysql" | "mariadb" => Ok(SqlDialect::Mysql),
+            v => Err(anyhow::anyhow!("Unsupported SQL dialect: {}", v)),
+        }
+    }
+}
+
+impl From<SqlDialect> for &'static str {
+    fn from(dialect: SqlDialect) -> Self {
+        match dialect {
+            SqlDialect::Sqlite => "sqlite",
+            SqlDialect::Postgres => "postgres",
+            SqlDialect::Mysql => "mysql",
+        }
+    }
+}
+
+impl fmt::Display for SqlDialect {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
+
+impl FromStr for MaskStrategy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "hash" => Ok(MaskStrategy::Hash),
+            "redact" => Ok(MaskStrategy::Redact),
+            "partial" => Ok(MaskStrategy::Partial),
+            "fake" => Ok(MaskStrategy::Fake),
+            v => Err(anyhow::anyhow!("Unsupported mask strategy: {}", v)),
+        }
+    }
+}
+
+impl From<MaskStrategy> for &'static str {
+    fn from(strategy: MaskStrategy) -> Self {
+        match strategy {
+            MaskStrategy::Hash => "hash",
+            MaskStrategy::Redact => "redact",
+            MaskStrategy::Partial => "partial",
+            MaskStrategy::Fake => "fake",
+        }
+    }
+}
+
+impl fmt::Display for MaskStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
+
+impl FromStr for Compression {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "none" | "uncompressed" => Ok(Compression::None),
+            "snappy" => Ok(Compression::Snappy),
+            "zstd" => Ok(Compression::Zstd),
+            v => Err(anyhow::anyhow!("Unsupported compression: {}", v)),
+        }
+    }
+}
+
+impl From<Compression> for &'static str {
+    fn from(compression: Compression) -> Self {
+        match compression {
+            Compression::None => "none",
+            Compression::Snappy => "snappy",
+            Compression::Zstd => "zstd",
+        }
+    }
+}
+
+impl fmt::Display for Compression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
+
+impl FromStr for OnError {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "skip" => Ok(OnError::Skip),
+            "fail" => Ok(OnError::Fail),
+            "quarantine" => Ok(OnError::Quarantine),
+            v => Err(anyhow::anyhow!("Unsupported on-error mode: {}", v)),
+        }
+    }
+}
+
+impl From<OnError> for &'static str {
+    fn from(on_error: OnError) -> Self {
+        match on_error {
+            OnError::Skip => "skip",
+            OnError::Fail => "fail",
+            OnError::Quarantine => "quarantine",
+        }
+    }
+}
+
+impl fmt::Display for OnError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
+
+impl FromStr for CsvJoinType {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "inner" => Ok(CsvJoinType::Inner),
+            "left" => Ok(CsvJoinType::Left),
+            "right" => Ok(CsvJoinType::Right),
+            "full" | "outer" => Ok(CsvJoinType::Full),
+            v => Err(anyhow::anyhow!("Unsupported join type: {}", v)),
+        }
+    }
+}
+
+impl From<CsvJoinType> for &'static str {
+    fn from(how: CsvJoinType) -> Self {
+        match how {
+            CsvJoinType::Inner => "inner",
+            CsvJoinType::Left => "left",
+            CsvJoinType::Right => "right",
+            CsvJoinType::Full => "full",
+        }
+    }
+}
+
+impl fmt::Display for CsvJoinType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
+
 impl CmdExecutor for CsvOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        match self.cmd {
+            Some(cmd) => cmd.execute().await,
+            None => self.convert.execute().await,
+        }
+    }
+}
+
+impl CmdExecutor for CsvConvertOpts {
     async fn execute(self) -> anyhow::Result<()> {
         let output = if let Some(output) = self.output {
             output.clone()
         } else {
             format!("output.{}", self.format)
         };
-        process_csv(&self.input, output, self.format)
+        // sqlite/sql 输出默认用输入文件名作为表名
+        let mut output_opts = self.output_opts;
+        if output_opts.table.is_none() && self.input != "-" {
+            output_opts.table = std::path::Path::new(&self.input)
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .map(|stem| stem.split('.').next().unwrap_or(stem).to_string());
+        }
+        // parquet/arrow 的列需要确定的类型, 默认推断
+        let mut types = self.types;
+        if let OutputFormat::Parquet | OutputFormat::Arrow = self.format {
+            types.infer = true;
+        }
+        process_csv(
+            &self.input,
+            output,
+            self.format,
+            &self.reader,
+            &types,
+            &self.filter,
+            &output_opts,
+            &self.errors,
+        )
+    }
+}
+
+impl CmdExecutor for CsvShowOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        process_csv_show(&self.input, &self.reader, &self.filter, &self.table)
+    }
+}
+
+impl CmdExecutor for CsvStatsOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        process_csv_stats(
+            &self.input,
+            &self.output,
+            self.format,
+            &self.reader,
+            &self.filter,
+            self.top,
+            self.approx,
+        )
+    }
+}
+
+impl CmdExecutor for CsvFromOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let format = match self.format {
+            Some(format) => format,
+            None => detect_format(&self.input)?,
+        };
+        process_csv_from(&self.input, &self.output, format, self.delimiter)
+    }
+}
+
+impl CmdExecutor for CsvSortOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        process_csv_sort(
+            &self.input,
+            &self.output,
+            &self.reader,
+            &self.filter,
+            &self.by,
+            self.memory,
+        )
+    }
+}
+
+impl CmdExecutor for CsvDedupeOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        process_csv_dedupe(
+            &self.input,
+            &self.output,
+            &self.reader,
+            &self.filter,
+            &self.key,
+        )
+    }
+}
+
+impl CmdExecutor for CsvSampleOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        process_csv_sample(
+            &self.input,
+            &self.output,
+            &self.reader,
+            &self.filter,
+            self.size,
+            self.seed,
+        )
+    }
+}
+
+impl CmdExecutor for CsvJoinOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        // 与 csv 命令一样, parquet/arrow 默认推断类型
+        let mut types = self.types;
+        if let Some(OutputFormat::Parquet | OutputFormat::Arrow) = self.format {
+            types.infer = true;
+        }
+        process_csv_join(
+            &self.left,
+            &self.right,
+            &self.output,
+            self.format,
+            self.how,
+            &self.on,
+            (&self.left_suffix, &self.right_suffix),
+            &self.reader,
+            &types,
+            &self.output_opts,
+        )
+    }
+}
+
+impl CmdExecutor for CsvDiffOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        process_csv_diff(
+            &self.old,
+            &self.new,
+            &self.output,
+            self.format,
+            &self.key,
+            &self.reader,
+        )
+    }
+}
+
+impl CmdExecutor for CsvQueryOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        process_csv_query(
+            &self.sql,
+            &self.table,
+            &self.output,
+            self.format,
+            &self.reader,
+        )
+    }
+}
+
+impl CmdExecutor for CsvValidateOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        process_csv_validate(
+            &self.input,
+            &self.schema,
+            &self.output,
+            self.format,
+            &self.reader,
+        )
+    }
+}
+
+impl CmdExecutor for CsvMaskOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        process_csv_mask(
+            &self.input,
+            &self.output,
+            &self.column,
+            self.key.as_deref(),
+            &self.reader,
+        )
+    }
+}
+
+impl CmdExecutor for CsvEncryptOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let opts = self.crypt;
+        process_csv_encrypt(
+            &opts.input,
+            &opts.output,
+            &opts.column,
+            &opts.key,
+            &opts.reader,
+        )
+    }
+}
+
+impl CmdExecutor for CsvDecryptOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let opts = self.crypt;
+        process_csv_decrypt(
+            &opts.input,
+            &opts.output,
+            &opts.column,
+            &opts.key,
+            &opts.reader,
+        )
+    }
+}
+
+impl CmdExecutor for CsvSplitOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        process_csv_split(
+            &self.input,
+            &self.output_dir,
+            self.prefix.as_deref(),
+            self.rows,
+            self.by_column.as_deref(),
+            &self.reader,
+        )
+    }
+}
+
+impl CmdExecutor for CsvCatOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        process_csv_cat(&self.inputs, &self.output, &self.reader)
+    }
+}
+
+fn detect_format(input: &str) -> Result<OutputFormat, anyhow::Error> {
+    std::path::Path::new(input)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .ok_or_else(|| anyhow::anyhow!("Cannot detect format of {}, use --format", input))?
+        .parse()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        parse_ascii, parse_cell_range, parse_column_type, parse_sort_key, CellRange, CsvSortKey,
+        CsvType,
+    };
+
+    #[test]
+    fn test_parse_ascii() {
+        assert_eq!(parse_ascii(";").unwrap(), b';');
+        assert_eq!(parse_ascii("\\t").unwrap(), b'\t');
+        assert!(parse_ascii("ab").is_err());
+        assert!(parse_ascii("é").is_err());
+    }
+
+    #[test]
+    fn test_parse_column_type() {
+        assert_eq!(
+            parse_column_type("Kit Number:int").unwrap(),
+            ("Kit Number".to_string(), CsvType::Integer)
+        );
+        assert!(parse_column_type("Kit Number").is_err());
+        assert!(parse_column_type("Kit Number:date").is_err());
+    }
+
+    #[test]
+    fn test_parse_sort_key() {
+        assert_eq!(
+            parse_sort_key("Kit Number:desc:num").unwrap(),
+            CsvSortKey {
+                column: "Kit Number".to_string(),
+                desc: true,
+                numeric: true,
+            }
+        );
+        assert_eq!(parse_sort_key("a:b").unwrap().column, "a:b");
+        assert!(parse_sort_key(":desc").is_err());
+    }
+
+    #[test]
+    fn test_parse_cell_range() {
+        assert_eq!(
+            parse_cell_range("B3:F20").unwrap(),
+            CellRange {
+                start: (2, 1),
+                end: Some((19, 5)),
+            }
+        );
+        assert_eq!(parse_cell_range("aa1").unwrap().start, (0, 26));
+        assert_eq!(parse_cell_range("B3:").unwrap().end, None);
+        assert!(parse_cell_range("F20:B3").is_err());
+        assert!(parse_cell_range("A0").is_err());
+        assert!(parse_cell_range("3B").is_err());
     }
 }
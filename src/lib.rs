@@ -5,7 +5,7 @@ mod utils;
 pub use cli::*;
 use enum_dispatch::enum_dispatch;
 pub use process::*;
-pub use utils::get_reader;
+pub use utils::{get_reader, get_writer};
 
 #[enum_dispatch]
 #[allow(async_fn_in_trait)]
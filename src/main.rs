@@ -9,7 +9,7 @@ async fn main() -> anyhow::Result<()> {
     let cli = Cli::parse();
 
     match cli.cmd {
-        SubCommand::Csv(opts) => opts.execute().await,
+        SubCommand::Csv(opts) => (*opts).execute().await,
         SubCommand::GenPass(opts) => opts.execute().await,
         SubCommand::Base64(cmd) => cmd.execute().await,
         SubCommand::Text(cmd) => cmd.execute().await,
@@ -1,13 +1,49 @@
 mod b64;
 mod chacha20;
+mod csv_arrow;
 mod csv_convert;
+mod csv_crypt;
+mod csv_decode;
+mod csv_diff;
+mod csv_filter;
+mod csv_fixed;
+mod csv_from;
+mod csv_infer;
+mod csv_join;
+mod csv_mask;
+mod csv_ops;
+mod csv_query;
+mod csv_reject;
+mod csv_sheet;
+mod csv_sniff;
+mod csv_split;
+mod csv_sql;
+mod csv_stats;
+mod csv_table;
+mod csv_unflatten;
+mod csv_validate;
+mod csv_writer;
 mod gen_pass;
 mod http;
 mod text;
 
+#[cfg(test)]
+mod test_utils;
+
 pub use b64::{b64_decode, b64_encode};
 pub use chacha20::{process_decrypt, process_encrypt};
 pub use csv_convert::process_csv;
+pub use csv_crypt::{process_csv_decrypt, process_csv_encrypt};
+pub use csv_diff::process_csv_diff;
+pub use csv_from::process_csv_from;
+pub use csv_join::process_csv_join;
+pub use csv_mask::process_csv_mask;
+pub use csv_ops::{process_csv_dedupe, process_csv_sample, process_csv_sort};
+pub use csv_query::process_csv_query;
+pub use csv_split::{process_csv_cat, process_csv_split};
+pub use csv_stats::process_csv_stats;
+pub use csv_table::process_csv_show;
+pub use csv_validate::process_csv_validate;
 pub use gen_pass::process_genpass;
 pub use http::process_http_serve;
 pub use text::{process_generate_keys, process_text_sign, process_text_verify};
@@ -1,33 +1,90 @@
+use std::fs;
+
 use anyhow::{anyhow, Result};
 use chacha20poly1305::{
-    aead::{Aead, AeadCore, KeyInit, OsRng},
-    ChaCha20Poly1305, Error as ChaCha20Error,
+    aead::{Aead, AeadCore, KeyInit, OsRng, Payload},
+    ChaCha20Poly1305, Error as ChaCha20Error, Nonce,
 };
 
 use crate::get_reader;
 
+// 96 位的 nonce 放在密文前面, 解密时从输入中取出
+const NONCE_LEN: usize = 12;
+
 pub fn process_encrypt(input: &str, key: &[u8]) -> Result<Vec<u8>> {
     let mut reader = get_reader(input)?;
-    let cipher = chacha20poly1305::ChaCha20Poly1305::new_from_slice(key)?;
-    let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let cipher = ChaCha20Poly1305::new_from_slice(key)?;
 
     let mut buf = Vec::new();
     reader.read_to_end(&mut buf)?;
-    let cipher_text = cipher
-        .encrypt(&nonce, buf.as_slice())
-        .map_err(|e: ChaCha20Error| anyhow!(format!("Error with chacha20poly1305: {:?}", e)))?;
-    Ok(cipher_text)
+    seal(&cipher, &buf, b"")
 }
 
 pub fn process_decrypt(input: &str, key: &[u8]) -> Result<Vec<u8>> {
     let mut reader = get_reader(input)?;
-    let cipher = chacha20poly1305::ChaCha20Poly1305::new_from_slice(key)?;
-    let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let cipher = ChaCha20Poly1305::new_from_slice(key)?;
 
     let mut buf = Vec::new();
     reader.read_to_end(&mut buf)?;
+    open(&cipher, &buf, b"")
+}
+
+// 密钥文件的前 32 个字节, 与 `rcli text generate` 生成的 blake3 密钥格式相同
+pub(crate) fn load_cipher(path: &str) -> Result<ChaCha20Poly1305> {
+    let key = fs::read(path)?;
+    let key = key
+        .get(..32)
+        .ok_or_else(|| anyhow!("ChaCha20 key must be at least 32 bytes"))?;
+    Ok(ChaCha20Poly1305::new_from_slice(key)?)
+}
+
+// 每次加密使用随机的 nonce, 返回 nonce + 密文; aad 不加密, 但解密时必须提供相同的值
+pub(crate) fn seal(cipher: &ChaCha20Poly1305, plain_text: &[u8], aad: &[u8]) -> Result<Vec<u8>> {
+    let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let payload = Payload {
+        msg: plain_text,
+        aad,
+    };
+    let cipher_text = cipher
+        .encrypt(&nonce, payload)
+        .map_err(|e: ChaCha20Error| anyhow!(format!("Error with chacha20poly1305: {:?}", e)))?;
+    let mut out = nonce.to_vec();
+    out.extend(cipher_text);
+    Ok(out)
+}
+
+pub(crate) fn open(cipher: &ChaCha20Poly1305, data: &[u8], aad: &[u8]) -> Result<Vec<u8>> {
+    if data.len() < NONCE_LEN {
+        return Err(anyhow!("Cipher text is too short"));
+    }
+    let (nonce, cipher_text) = data.split_at(NONCE_LEN);
+    let payload = Payload {
+        msg: cipher_text,
+        aad,
+    };
     let plain_text = cipher
-        .decrypt(&nonce, buf.as_slice())
+        .decrypt(Nonce::from_slice(nonce), payload)
         .map_err(|e: ChaCha20Error| anyhow!(format!("Error with chacha20poly1305: {:?}", e)))?;
     Ok(plain_text)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{super::test_utils::temp_file, *};
+
+    #[test]
+    fn test_encrypt_decrypt() {
+        let cipher = load_cipher("fixture/blake3.txt").unwrap();
+        let sealed = seal(&cipher, b"hello!", b"Name").unwrap();
+        assert_eq!(sealed.len(), NONCE_LEN + 6 + 16);
+        assert_ne!(sealed, seal(&cipher, b"hello!", b"Name").unwrap());
+        assert_eq!(open(&cipher, &sealed, b"Name").unwrap(), b"hello!");
+        assert!(open(&cipher, &sealed, b"DOB").is_err());
+
+        let key = fs::read("fixture/blake3.txt").unwrap();
+        let encrypted = process_encrypt("fixture/b64.txt", &key[..32]).unwrap();
+        let file = temp_file(&encrypted);
+        let decrypted = process_decrypt(file.path().to_str().unwrap(), &key[..32]).unwrap();
+        assert_eq!(decrypted, fs::read("fixture/b64.txt").unwrap());
+    }
+}
@@ -0,0 +1,279 @@
+use std::{io::BufWriter, sync::Arc};
+
+use anyhow::{anyhow, Result};
+use arrow_array::{
+    builder::{BooleanBuilder, Float64Builder, Int64Builder, StringBuilder},
+    ArrayRef, RecordBatch,
+};
+use arrow_ipc::{
+    writer::{FileWriter, IpcWriteOptions},
+    CompressionType,
+};
+use arrow_schema::{DataType, Field, Schema, SchemaRef};
+use parquet::{
+    arrow::ArrowWriter,
+    basic::{Compression as ParquetCompression, ZstdLevel},
+    file::properties::WriterProperties,
+};
+use serde_json::Value;
+
+use super::{
+    csv_sql::{text_of, SqlKind, Stage},
+    csv_writer::RecordWriter,
+};
+use crate::{
+    cli::{Compression, CsvOutputOpts, OutputFormat},
+    get_writer,
+};
+
+// 列类型需要看完所有记录才能确定, 与 sql 输出一样先暂存, finish 时按 row group 写出
+pub(crate) struct ColumnarWriter {
+    output: String,
+    format: OutputFormat,
+    compression: Compression,
+    row_group_size: usize,
+    stage: Option<Stage>,
+}
+
+pub(crate) fn columnar_writer(
+    format: OutputFormat,
+    output: &str,
+    opts: &CsvOutputOpts,
+) -> Result<ColumnarWriter> {
+    let compression = match (format, opts.compression) {
+        (OutputFormat::Arrow, Some(Compression::Snappy)) => {
+            return Err(anyhow!("Arrow IPC supports only zstd compression"))
+        }
+        (_, Some(compression)) => compression,
+        (OutputFormat::Parquet, None) => Compression::Snappy,
+        (_, None) => Compression::None,
+    };
+    Ok(ColumnarWriter {
+        output: output.to_string(),
+        format,
+        compression,
+        row_group_size: opts.row_group_size.max(1),
+        stage: None,
+    })
+}
+
+impl RecordWriter for ColumnarWriter {
+    fn write(&mut self, record: Value) -> Result<()> {
+        Stage::push(&mut self.stage, record)
+    }
+
+    fn columns(&mut self, columns: &[String]) -> Result<()> {
+        Stage::declare(&mut self.stage, columns)
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        let stage = match self.stage.take() {
+            Some(stage) => stage,
+            // 没有记录也不知道列名时, 输出一个没有列的合法文件
+            None => Stage::new(Vec::new())?,
+        };
+        let kinds = stage.kinds.clone();
+        let schema = arrow_schema(&stage.columns, &kinds);
+        let writer = BufWriter::new(get_writer(&self.output)?);
+        let mut sink = match self.format {
+            OutputFormat::Parquet => {
+                let compression = match self.compression {
+                    Compression::None => ParquetCompression::UNCOMPRESSED,
+                    Compression::Snappy => ParquetCompression::SNAPPY,
+                    Compression::Zstd => ParquetCompression::ZSTD(ZstdLevel::default()),
+                };
+                let props = WriterProperties::builder()
+                    .set_compression(compression)
+                    .set_max_row_group_size(self.row_group_size)
+                    .build();
+                Sink::Parquet(ArrowWriter::try_new(writer, schema.clone(), Some(props))?)
+            }
+            _ => {
+                let compression = match self.compression {
+                    Compression::Zstd => Some(CompressionType::ZSTD),
+                    _ => None,
+                };
+                let options = IpcWriteOptions::default().try_with_compression(compression)?;
+                Sink::Ipc(FileWriter::try_new_with_options(writer, &schema, options)?)
+            }
+        };
+
+        let mut rows = stage.rows()?.peekable();
+        while rows.peek().is_some() {
+            let chunk = rows
+                .by_ref()
+                .take(self.row_group_size)
+                .collect::<Result<Vec<_>>>()?;
+            let batch = record_batch(schema.clone(), &kinds, &chunk)?;
+            match &mut sink {
+                Sink::Parquet(w) => w.write(&batch)?,
+                Sink::Ipc(w) => w.write(&batch)?,
+            }
+        }
+        match sink {
+            Sink::Parquet(w) => {
+                w.close()?;
+            }
+            Sink::Ipc(mut w) => w.finish()?,
+        }
+        Ok(())
+    }
+}
+
+type Output = BufWriter<Box<dyn std::io::Write + Send>>;
+
+enum Sink {
+    Parquet(ArrowWriter<Output>),
+    Ipc(FileWriter<Output>),
+}
+
+// 所有列都允许为空; 全为空的列和混合类型的列都当作字符串
+fn arrow_schema(columns: &[String], kinds: &[SqlKind]) -> SchemaRef {
+    let fields: Vec<Field> = columns
+        .iter()
+        .zip(kinds)
+        .map(|(name, kind)| Field::new(name, data_type(*kind), true))
+        .collect();
+    Arc::new(Schema::new(fields))
+}
+
+fn data_type(kind: SqlKind) -> DataType {
+    match kind {
+        SqlKind::Integer => DataType::Int64,
+        SqlKind::Real => DataType::Float64,
+        SqlKind::Boolean => DataType::Boolean,
+        SqlKind::Null | SqlKind::Text => DataType::Utf8,
+    }
+}
+
+fn record_batch(schema: SchemaRef, kinds: &[SqlKind], rows: &[Vec<Value>]) -> Result<RecordBatch> {
+    let columns: Vec<ArrayRef> = kinds
+        .iter()
+        .enumerate()
+        .map(|(i, kind)| {
+            let cells = rows.iter().map(|row| &row[i]);
+            let array: ArrayRef = match kind {
+                SqlKind::Integer => {
+                    let mut b = Int64Builder::with_capacity(rows.len());
+                    cells.for_each(|v| b.append_option(v.as_i64()));
+                    Arc::new(b.finish())
+                }
+                SqlKind::Real => {
+                    let mut b = Float64Builder::with_capacity(rows.len());
+                    cells.for_each(|v| b.append_option(v.as_f64()));
+                    Arc::new(b.finish())
+                }
+                SqlKind::Boolean => {
+                    let mut b = BooleanBuilder::with_capacity(rows.len());
+                    cells.for_each(|v| b.append_option(v.as_bool()));
+                    Arc::new(b.finish())
+                }
+                SqlKind::Null | SqlKind::Text => {
+                    let mut b = StringBuilder::new();
+                    for v in cells {
+                        match v {
+                            Value::Null => b.append_null(),
+                            v => b.append_value(text_of(v)),
+                        }
+                    }
+                    Arc::new(b.finish())
+                }
+            };
+            array
+        })
+        .collect();
+    let batch = match columns.is_empty() {
+        true => RecordBatch::new_empty(schema),
+        false => RecordBatch::try_new(schema, columns)?,
+    };
+    Ok(batch)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+
+    use arrow_array::{Array, BooleanArray, Float64Array, Int64Array, StringArray};
+    use arrow_ipc::reader::FileReader;
+    use parquet::{
+        arrow::arrow_reader::ParquetRecordBatchReaderBuilder, file::reader::FileReader as _,
+    };
+    use serde_json::json;
+    use tempfile::NamedTempFile;
+
+    use super::*;
+
+    fn write(format: OutputFormat, opts: &CsvOutputOpts) -> NamedTempFile {
+        let file = NamedTempFile::new().unwrap();
+        let mut writer = columnar_writer(format, file.path().to_str().unwrap(), opts).unwrap();
+        for record in [
+            json!({"name": "Buffon", "kit": 77, "rating": 88, "captain": true}),
+            json!({"name": "Dybala", "kit": 10, "rating": 89.5, "captain": null}),
+            json!({"name": null, "kit": null, "rating": 80, "captain": false}),
+        ] {
+            writer.write(record).unwrap();
+        }
+        writer.finish().unwrap();
+        file
+    }
+
+    fn check(batch: &RecordBatch) {
+        let schema = batch.schema();
+        let types: Vec<&DataType> = schema.fields().iter().map(|f| f.data_type()).collect();
+        assert_eq!(
+            types,
+            [
+                &DataType::Utf8,
+                &DataType::Int64,
+                &DataType::Float64,
+                &DataType::Boolean
+            ]
+        );
+        let col = |i: usize| batch.column(i).as_any();
+        let names = col(0).downcast_ref::<StringArray>().unwrap();
+        assert_eq!(names.value(1), "Dybala");
+        assert!(names.is_null(2));
+        let kits = col(1).downcast_ref::<Int64Array>().unwrap();
+        assert_eq!(kits.value(0), 77);
+        let ratings = col(2).downcast_ref::<Float64Array>().unwrap();
+        assert_eq!(ratings.value(1), 89.5);
+        let captains = col(3).downcast_ref::<BooleanArray>().unwrap();
+        assert!(captains.value(0));
+        assert!(captains.is_null(1));
+    }
+
+    #[test]
+    fn test_parquet_output() {
+        let opts = CsvOutputOpts {
+            compression: Some(Compression::Zstd),
+            row_group_size: 2,
+            ..Default::default()
+        };
+        let file = write(OutputFormat::Parquet, &opts);
+        let reader =
+            parquet::file::reader::SerializedFileReader::new(File::open(file.path()).unwrap())
+                .unwrap();
+        assert_eq!(reader.metadata().num_row_groups(), 2);
+
+        let mut batches =
+            ParquetRecordBatchReaderBuilder::try_new(File::open(file.path()).unwrap())
+                .unwrap()
+                .with_batch_size(10)
+                .build()
+                .unwrap();
+        check(&batches.next().unwrap().unwrap());
+    }
+
+    #[test]
+    fn test_arrow_output() {
+        let file = write(OutputFormat::Arrow, &CsvOutputOpts::default());
+        let mut reader = FileReader::try_new(File::open(file.path()).unwrap(), None).unwrap();
+        check(&reader.next().unwrap().unwrap());
+
+        let opts = CsvOutputOpts {
+            compression: Some(Compression::Snappy),
+            ..Default::default()
+        };
+        assert!(columnar_writer(OutputFormat::Arrow, "-", &opts).is_err());
+    }
+}
@@ -1,45 +1,347 @@
-use std::fs;
-
-use csv::Reader;
-use serde::{Deserialize, Serialize};
-use serde_json::Value;
-
-use crate::cli::OutputFormat;
-
-#[derive(Debug, Serialize, Deserialize)]
-#[serde(rename_all = "PascalCase")]
-struct Player {
-    name: String,
-    position: String,
-    #[serde(rename = "DOB")]
-    dob: String,
-    nationality: String,
-    #[serde(rename = "Kit Number")]
-    kit: u8,
-}
-
-pub fn process_csv(input: &str, output: String, format: OutputFormat) -> anyhow::Result<()> {
-    let mut reader = Reader::from_path(input)?;
-    let mut ret = Vec::with_capacity(128);
-    let headers = reader.headers()?.clone();
-    for res in reader.records() {
-        let record = res?;
-        let json_value = headers.iter().zip(record.iter()).collect::<Value>();
-        ret.push(json_value);
-    }
-
-    let content = match format {
-        OutputFormat::Json => serde_json::to_string_pretty(&ret)?,
-        OutputFormat::Yaml => serde_yaml::to_string(&ret)?,
-        OutputFormat::Toml => {
-            #[derive(Serialize)]
-            struct A {
-                a: Vec<Value>,
+use std::io::Read;
+
+use csv::{ByteRecord, Position, Reader, ReaderBuilder, StringRecord, Trim};
+
+use super::{
+    csv_decode::decode_input,
+    csv_filter::{Projection, RowFilter},
+    csv_fixed::FixedWidthSpec,
+    csv_infer::CellTyper,
+    csv_reject::{BadRow, RowErrors},
+    csv_sheet::{is_spreadsheet, read_sheet},
+    csv_sniff::sniff_reader,
+    csv_unflatten::Unflatten,
+    csv_writer::record_writer,
+};
+use crate::{
+    cli::{
+        CsvErrorOpts, CsvFilterOpts, CsvOutputOpts, CsvReaderOpts, CsvTrim, CsvType, CsvTypeOpts,
+        OutputFormat,
+    },
+    get_reader,
+};
+
+// 读取 csv 并应用 --where/--select, 其他 csv 子命令都基于它迭代记录
+pub(crate) struct CsvRows {
+    reader: Reader<Box<dyn Read>>,
+    headers: StringRecord,
+    selected: Option<StringRecord>,
+    // 实际读取时使用的配置, 已经应用了 --sniff 检测到的方言
+    dialect: CsvReaderOpts,
+    filter: Option<RowFilter>,
+    projection: Option<Projection>,
+    // 最近一条记录的位置, 以及读到的行数 (包括被过滤和无法解析的行)
+    position: Position,
+    read: u64,
+    filtered: u64,
+    // 指定了 --select 时保存最近一条记录的全部字段, 坏行按原始的列写出
+    current: Option<StringRecord>,
+    trim_fields: bool,
+    // 表头在输入中的行号, 没有表头或表头是生成的时为 None
+    header_line: Option<u64>,
+    // 定宽文件跳过的行数; 转换后的第一行是生成的表头, 不对应输入中的行
+    skipped_lines: Option<u64>,
+    // 定宽文件列定义中声明的类型
+    types: Vec<(String, CsvType)>,
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn process_csv(
+    input: &str,
+    output: String,
+    format: OutputFormat,
+    opts: &CsvReaderOpts,
+    types: &CsvTypeOpts,
+    filter: &CsvFilterOpts,
+    output_opts: &CsvOutputOpts,
+    errors: &CsvErrorOpts,
+) -> anyhow::Result<()> {
+    let mut rows = CsvRows::open(input, opts, filter)?;
+    let mut rejects = RowErrors::new(errors, rows.source_headers())?;
+    let mut writer = record_writer(format, &output, output_opts)?;
+    let typer = CellTyper::new(rows.headers(), &rows.type_opts(types))?;
+    let unflatten = match output_opts.unflatten {
+        true => Some(Unflatten::new(rows.headers())?),
+        false => None,
+    };
+    if unflatten.is_none() {
+        let columns: Vec<String> = rows.headers().iter().map(String::from).collect();
+        writer.columns(&columns)?;
+    }
+    let mut written = 0;
+    while let Some(record) = rows.next() {
+        let mut json_value = match record.and_then(|record| {
+            typer
+                .row(rows.headers(), &record)
+                .map_err(|e| rows.bad_row(&record, e.to_string()).into())
+        }) {
+            Ok(value) => value,
+            Err(e) => {
+                rejects.handle(e)?;
+                continue;
             }
-            let data = A { a: ret };
-            toml::to_string_pretty(&data)?
+        };
+        if let Some(unflatten) = &unflatten {
+            json_value = unflatten.apply(json_value);
         }
-    };
-    fs::write(output, content)?;
-    Ok(())
+        writer.write(json_value)?;
+        written += 1;
+    }
+    writer.finish()?;
+    rejects.finish(rows.read(), rows.filtered(), written, opts.verbose)
+}
+
+impl CsvRows {
+    pub(crate) fn open(
+        input: &str,
+        opts: &CsvReaderOpts,
+        filter: &CsvFilterOpts,
+    ) -> anyhow::Result<Self> {
+        let mut input = if is_spreadsheet(input) {
+            read_sheet(input, opts)?
+        } else if opts.sheet.is_some() || opts.range.is_some() {
+            anyhow::bail!("--sheet and --range only apply to xlsx/xls/ods input");
+        } else {
+            decode_input(get_reader(input)?, opts.encoding, opts.verbose)?
+        };
+        let mut opts = opts.clone();
+        let mut types = Vec::new();
+        let mut skipped_lines = None;
+        if let Some(spec) = &opts.fixed_width {
+            let spec = FixedWidthSpec::load(spec)?;
+            types = spec.types()?;
+            skipped_lines = Some(spec.skip() as u64);
+            input = Box::new(spec.reader(input)?);
+            // 转换后的内容总是标准的 csv
+            opts = CsvReaderOpts {
+                trim: opts.trim,
+                verbose: opts.verbose,
+                ..Default::default()
+            };
+        }
+        if opts.sniff {
+            let (sniffed, dialect) = sniff_reader(input)?;
+            if opts.verbose {
+                eprintln!("Sniffed dialect: {}", dialect);
+            }
+            dialect.apply(&mut opts);
+            input = sniffed;
+        }
+        let mut reader = build_reader(&opts).from_reader(input);
+        let headers = read_headers(&mut reader, opts.header)?;
+        let header_line = match (opts.header, skipped_lines) {
+            (true, None) => reader.headers()?.position().map(|p| p.line()),
+            _ => None,
+        };
+        let filter_expr = match &filter.filter {
+            Some(expr) => Some(RowFilter::parse(expr, &headers)?),
+            None => None,
+        };
+        let projection = if filter.select.is_empty() {
+            None
+        } else {
+            Some(Projection::new(&headers, &filter.select)?)
+        };
+        Ok(Self {
+            reader,
+            selected: projection.as_ref().map(|p| p.apply(&headers)),
+            headers,
+            filter: filter_expr,
+            projection,
+            position: Position::new(),
+            read: 0,
+            filtered: 0,
+            current: None,
+            trim_fields: matches!(opts.trim, CsvTrim::Fields | CsvTrim::All),
+            header_line,
+            skipped_lines,
+            types,
+            dialect: opts,
+        })
+    }
+
+    // 定宽文件声明的类型作为默认值, 命令行的 --types 优先
+    pub(crate) fn type_opts(&self, opts: &CsvTypeOpts) -> CsvTypeOpts {
+        let headers = self.headers();
+        let types = self
+            .types
+            .iter()
+            .filter(|(name, _)| headers.iter().any(|h| h == name))
+            .chain(&opts.types)
+            .cloned()
+            .collect();
+        CsvTypeOpts {
+            infer: opts.infer,
+            types,
+        }
+    }
+
+    // 写 csv 时用它代替命令行的配置, 输出才能保持输入的分隔符、引号和有无表头
+    pub(crate) fn dialect(&self) -> &CsvReaderOpts {
+        &self.dialect
+    }
+
+    // 输出记录对应的表头, 指定了 --select 时只包含选中的列
+    pub(crate) fn headers(&self) -> &StringRecord {
+        self.selected.as_ref().unwrap_or(&self.headers)
+    }
+
+    // 最近一条记录在输入中的行号, 用于错误提示
+    pub(crate) fn line(&self) -> u64 {
+        let line = self.position.line();
+        match self.skipped_lines {
+            None => line,
+            Some(n) => (line + n).saturating_sub(1),
+        }
+    }
+
+    pub(crate) fn header_line(&self) -> Option<u64> {
+        self.header_line
+    }
+
+    pub(crate) fn read(&self) -> u64 {
+        self.read
+    }
+
+    // 被 --where 过滤掉的行数
+    pub(crate) fn filtered(&self) -> u64 {
+        self.filtered
+    }
+
+    // 输入中的全部列, 不受 --select 影响
+    pub(crate) fn source_headers(&self) -> &StringRecord {
+        &self.headers
+    }
+
+    // 最近一条记录可以解析但内容有误, 比如无法转换成声明的类型; 写出的是 --select 之前的原始字段
+    pub(crate) fn bad_row(&self, record: &StringRecord, reason: String) -> BadRow {
+        let record = match &self.projection {
+            Some(_) => self.current.as_ref().unwrap_or(record),
+            None => record,
+        };
+        BadRow::new(Some(&self.position), reason, record)
+    }
+}
+
+impl Iterator for CsvRows {
+    type Item = anyhow::Result<StringRecord>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            // 先按字节读取, 这样 UTF-8 无效的行也能保留原始内容和位置
+            let mut raw = ByteRecord::new();
+            let result = self.reader.read_byte_record(&mut raw);
+            if let Some(pos) = raw.position() {
+                self.position = pos.clone();
+            }
+            match result {
+                Ok(true) => self.read += 1,
+                Ok(false) => return None,
+                Err(e) => {
+                    self.read += 1;
+                    return Some(Err(BadRow::from_csv(e, &raw)));
+                }
+            }
+            let mut record = match StringRecord::from_byte_record(raw) {
+                Ok(record) => record,
+                Err(e) => return Some(Err(BadRow::from_utf8(e))),
+            };
+            // 与 read_record 一致, 字节层面只去掉 ASCII 空白
+            if self.trim_fields {
+                record.trim();
+            }
+            if !self.dialect.header {
+                // flexible 模式下后面的行可能比第一行更长
+                for i in self.headers.len()..record.len() {
+                    self.headers.push_field(&format!("col{}", i + 1));
+                }
+            }
+            if self.filter.as_ref().is_some_and(|f| !f.matches(&record)) {
+                self.filtered += 1;
+                continue;
+            }
+            return Some(Ok(match &self.projection {
+                Some(p) => {
+                    let projected = p.apply(&record);
+                    self.current = Some(record);
+                    projected
+                }
+                None => record,
+            }));
+        }
+    }
+}
+
+pub(crate) fn build_reader(opts: &CsvReaderOpts) -> ReaderBuilder {
+    let mut builder = ReaderBuilder::new();
+    builder
+        .delimiter(opts.delimiter)
+        .has_headers(opts.header)
+        .quote(opts.quote)
+        .escape(opts.escape)
+        // 指定了 escape 时, 不再把 `""` 视为转义的引号
+        .double_quote(opts.escape.is_none())
+        .comment(opts.comment)
+        .flexible(opts.flexible)
+        .trim(opts.trim.into());
+    builder
+}
+
+// 没有表头时, 按照第一行的列数生成 col1, col2, ...
+pub(crate) fn read_headers<R: Read>(
+    reader: &mut Reader<R>,
+    has_header: bool,
+) -> anyhow::Result<StringRecord> {
+    let headers = reader.headers()?;
+    if has_header {
+        Ok(headers.clone())
+    } else {
+        Ok((1..=headers.len()).map(|i| format!("col{}", i)).collect())
+    }
+}
+
+impl From<CsvTrim> for Trim {
+    fn from(trim: CsvTrim) -> Self {
+        match trim {
+            CsvTrim::None => Trim::None,
+            CsvTrim::Headers => Trim::Headers,
+            CsvTrim::Fields => Trim::Fields,
+            CsvTrim::All => Trim::All,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_reader_with_dialect() {
+        let opts = CsvReaderOpts {
+            delimiter: b';',
+            comment: Some(b'#'),
+            trim: CsvTrim::All,
+            ..Default::default()
+        };
+        let data = "# exported\nname ; kit\nBuffon ; 77\n";
+        let mut reader = build_reader(&opts).from_reader(data.as_bytes());
+        let headers = read_headers(&mut reader, opts.header).unwrap();
+        assert_eq!(headers, vec!["name", "kit"]);
+        let record = reader.records().next().unwrap().unwrap();
+        assert_eq!(record, vec!["Buffon", "77"]);
+    }
+
+    #[test]
+    fn test_read_headers_without_header_row() {
+        let opts = CsvReaderOpts {
+            header: false,
+            ..Default::default()
+        };
+        let data = "Buffon,Goalkeeper,77\nChiellini,Defender,3\n";
+        let mut reader = build_reader(&opts).from_reader(data.as_bytes());
+        let headers = read_headers(&mut reader, opts.header).unwrap();
+        assert_eq!(headers, vec!["col1", "col2", "col3"]);
+        // 第一行不能被当作表头吞掉
+        assert_eq!(reader.records().count(), 2);
+    }
 }
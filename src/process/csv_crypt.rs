@@ -0,0 +1,149 @@
+use anyhow::{anyhow, Result};
+use base64::{prelude::BASE64_STANDARD, Engine};
+use chacha20poly1305::ChaCha20Poly1305;
+use csv::StringRecord;
+
+use super::{
+    chacha20::{load_cipher, open, seal},
+    csv_convert::CsvRows,
+    csv_ops::{column_indices, csv_writer},
+};
+use crate::cli::{CsvFilterOpts, CsvReaderOpts};
+
+// 选中列的单元格写成 base64(nonce + 密文), 其余列保持原样
+// 列名作为附加数据参与认证, 密文被移到其他列时无法解密; 同一列内不同行的密文互换无法发现
+pub fn process_csv_encrypt(
+    input: &str,
+    output: &str,
+    columns: &[String],
+    key: &str,
+    opts: &CsvReaderOpts,
+) -> Result<()> {
+    let cipher = load_cipher(key)?;
+    transform(input, output, columns, opts, |column, cell| {
+        Ok(BASE64_STANDARD.encode(seal(&cipher, cell.as_bytes(), column.as_bytes())?))
+    })
+}
+
+pub fn process_csv_decrypt(
+    input: &str,
+    output: &str,
+    columns: &[String],
+    key: &str,
+    opts: &CsvReaderOpts,
+) -> Result<()> {
+    let cipher = load_cipher(key)?;
+    transform(input, output, columns, opts, |column, cell| {
+        decrypt_cell(&cipher, column, cell)
+    })
+}
+
+fn decrypt_cell(cipher: &ChaCha20Poly1305, column: &str, cell: &str) -> Result<String> {
+    let data = BASE64_STANDARD
+        .decode(cell.trim())
+        .map_err(|_| anyhow!("not a base64 encrypted value"))?;
+    let plain = open(cipher, &data, column.as_bytes())
+        .map_err(|_| anyhow!("wrong key, corrupted value or value moved from another column"))?;
+    Ok(String::from_utf8(plain)?)
+}
+
+// 空单元格保持为空, 方便区分缺失值
+fn transform(
+    input: &str,
+    output: &str,
+    columns: &[String],
+    opts: &CsvReaderOpts,
+    f: impl Fn(&str, &str) -> Result<String>,
+) -> Result<()> {
+    let mut rows = CsvRows::open(input, opts, &CsvFilterOpts::default())?;
+    let headers = rows.headers().clone();
+    let indices = column_indices(&headers, columns)?;
+    let mut writer = csv_writer(output, rows.dialect(), &headers)?;
+    while let Some(record) = rows.next() {
+        let mut fields: Vec<String> = record?.iter().map(String::from).collect();
+        for &i in &indices {
+            match fields.get_mut(i) {
+                Some(cell) if !cell.is_empty() => {
+                    *cell = f(&headers[i], cell).map_err(|e| {
+                        anyhow!("line {}, column '{}': {}", rows.line(), &headers[i], e)
+                    })?;
+                }
+                _ => {}
+            }
+        }
+        writer.write_record(&StringRecord::from(fields))?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use tempfile::NamedTempFile;
+
+    use super::{super::test_utils::temp_file, *};
+
+    fn run(
+        f: fn(&str, &str, &[String], &str, &CsvReaderOpts) -> Result<()>,
+        input: &str,
+        columns: &[&str],
+    ) -> Result<String> {
+        let out = NamedTempFile::new().unwrap();
+        let columns: Vec<String> = columns.iter().map(|c| c.to_string()).collect();
+        f(
+            input,
+            out.path().to_str().unwrap(),
+            &columns,
+            "fixture/blake3.txt",
+            &CsvReaderOpts::default(),
+        )?;
+        Ok(fs::read_to_string(out.path()).unwrap())
+    }
+
+    #[test]
+    fn test_csv_encrypt_roundtrip() {
+        let encrypted = run(process_csv_encrypt, "assets/juventus.csv", &["Name", "DOB"]).unwrap();
+        let mut lines = encrypted.lines();
+        assert_eq!(
+            lines.next(),
+            Some("Name,Position,DOB,Nationality,Kit Number")
+        );
+        let first: Vec<&str> = lines.next().unwrap().split(',').collect();
+        assert_eq!(first.len(), 5);
+        assert_ne!(first[0], "Wojciech Szczesny");
+        assert_eq!(
+            (first[1], first[3], first[4]),
+            ("Goalkeeper", "Poland", "1")
+        );
+
+        let file = temp_file(&encrypted);
+        let input = file.path().to_str().unwrap();
+        let decrypted = run(process_csv_decrypt, input, &["Name", "DOB"]).unwrap();
+        assert_eq!(
+            decrypted,
+            fs::read_to_string("assets/juventus.csv").unwrap()
+        );
+
+        let err = run(process_csv_decrypt, input, &["Position"]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "line 2, column 'Position': not a base64 encrypted value"
+        );
+
+        // 交换两列的名称, 相当于把密文移到了另一列
+        let swapped = encrypted.replacen("Name,Position,DOB", "DOB,Position,Name", 1);
+        let file = temp_file(&swapped);
+        let err = run(
+            process_csv_decrypt,
+            file.path().to_str().unwrap(),
+            &["Name"],
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "line 2, column 'Name': wrong key, corrupted value or value moved from another column"
+        );
+    }
+}
@@ -0,0 +1,163 @@
+use std::io::{Cursor, Read};
+
+use anyhow::Result;
+use bzip2::read::MultiBzDecoder;
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8, WINDOWS_1252};
+use encoding_rs_io::DecodeReaderBytesBuilder;
+use flate2::read::MultiGzDecoder;
+use xz2::read::XzDecoder;
+
+// 检测编码时读取的字节数
+const ENCODING_SAMPLE: usize = 8 * 1024;
+
+// 按照文件头的魔数解压, 然后把非 UTF-8 的内容转码成 UTF-8
+pub(crate) fn decode_input(
+    input: Box<dyn Read>,
+    encoding: Option<&'static Encoding>,
+    verbose: bool,
+) -> Result<Box<dyn Read>> {
+    let input = decompress(input, verbose)?;
+    let (sample, input) = peek(input, ENCODING_SAMPLE)?;
+    let encoding = encoding.or_else(|| detect_encoding(&sample));
+    if verbose {
+        eprintln!("Encoding: {}", encoding.unwrap_or(UTF_8).name());
+    }
+    // UTF-8 原样交给 csv crate, 它会自己去掉 BOM
+    match encoding {
+        Some(encoding) if encoding != UTF_8 => Ok(Box::new(
+            DecodeReaderBytesBuilder::new()
+                .encoding(Some(encoding))
+                .build(input),
+        )),
+        _ => Ok(input),
+    }
+}
+
+fn decompress(input: Box<dyn Read>, verbose: bool) -> Result<Box<dyn Read>> {
+    let (magic, input) = peek(input, 6)?;
+    let (name, reader): (&str, Box<dyn Read>) = match magic.as_slice() {
+        [0x1f, 0x8b, ..] => ("gzip", Box::new(MultiGzDecoder::new(input))),
+        [0x28, 0xb5, 0x2f, 0xfd, ..] => ("zstd", Box::new(zstd::Decoder::new(input)?)),
+        [b'B', b'Z', b'h', ..] => ("bzip2", Box::new(MultiBzDecoder::new(input))),
+        [0xfd, b'7', b'z', b'X', b'Z', 0x00] => {
+            ("xz", Box::new(XzDecoder::new_multi_decoder(input)))
+        }
+        _ => return Ok(input),
+    };
+    if verbose {
+        eprintln!("Compression: {}", name);
+    }
+    Ok(reader)
+}
+
+// 读取开头的 n 个字节, 返回的 reader 仍然从头开始
+pub(crate) fn peek(mut input: Box<dyn Read>, n: usize) -> Result<(Vec<u8>, Box<dyn Read>)> {
+    let mut head = Vec::with_capacity(n);
+    input.by_ref().take(n as u64).read_to_end(&mut head)?;
+    let reader = Box::new(Cursor::new(head.clone()).chain(input));
+    Ok((head, reader))
+}
+
+// 优先看 BOM; 没有 BOM 时根据 0 字节的位置判断 UTF-16.
+// 不是合法 UTF-8 且没有任何多字节字符时才按 Windows-1252 处理, 否则个别坏行交给 --on-error
+fn detect_encoding(sample: &[u8]) -> Option<&'static Encoding> {
+    if let Some((encoding, _)) = Encoding::for_bom(sample) {
+        return Some(encoding);
+    }
+    let pairs = sample.len() / 2;
+    if pairs > 0 {
+        let even = sample.iter().step_by(2).filter(|b| **b == 0).count();
+        let odd = sample
+            .iter()
+            .skip(1)
+            .step_by(2)
+            .filter(|b| **b == 0)
+            .count();
+        // ASCII 字符的高位字节是 0; 非拉丁字符也可能有 0 字节, 所以只要求另一侧明显更少
+        if odd * 5 > pairs && even * 10 < odd {
+            return Some(UTF_16LE);
+        }
+        if even * 5 > pairs && odd * 10 < even {
+            return Some(UTF_16BE);
+        }
+    }
+    match std::str::from_utf8(sample) {
+        Ok(_) => None,
+        // 样本截断在多字节字符中间时不算错误
+        Err(e) if e.error_len().is_none() => None,
+        Err(_) if has_multibyte_utf8(sample) => None,
+        Err(_) => Some(WINDOWS_1252),
+    }
+}
+
+fn has_multibyte_utf8(sample: &[u8]) -> bool {
+    sample
+        .utf8_chunks()
+        .any(|chunk| chunk.valid().chars().any(|c| c.len_utf8() > 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::*;
+
+    fn decode(data: Vec<u8>) -> String {
+        let mut out = String::new();
+        decode_input(Box::new(Cursor::new(data)), None, false)
+            .unwrap()
+            .read_to_string(&mut out)
+            .unwrap();
+        out
+    }
+
+    const CSV: &str = "Name,Nationality\nGonzalo Higuaín,Argentina\n";
+
+    #[test]
+    fn test_decode_compressed() {
+        let mut gz = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        gz.write_all(CSV.as_bytes()).unwrap();
+        assert_eq!(decode(gz.finish().unwrap()), CSV);
+
+        let zst = zstd::encode_all(CSV.as_bytes(), 0).unwrap();
+        assert_eq!(decode(zst), CSV);
+
+        let mut bz = bzip2::write::BzEncoder::new(Vec::new(), bzip2::Compression::default());
+        bz.write_all(CSV.as_bytes()).unwrap();
+        assert_eq!(decode(bz.finish().unwrap()), CSV);
+
+        let mut xz = xz2::write::XzEncoder::new(Vec::new(), 6);
+        xz.write_all(CSV.as_bytes()).unwrap();
+        assert_eq!(decode(xz.finish().unwrap()), CSV);
+    }
+
+    #[test]
+    fn test_decode_encodings() {
+        let utf16le: Vec<u8> = CSV.encode_utf16().flat_map(u16::to_le_bytes).collect();
+        assert_eq!(decode(utf16le.clone()), CSV);
+        let mut with_bom = vec![0xff, 0xfe];
+        with_bom.extend(utf16le);
+        assert_eq!(decode(with_bom), CSV);
+
+        let utf16be: Vec<u8> = CSV.encode_utf16().flat_map(u16::to_be_bytes).collect();
+        assert_eq!(decode(utf16be), CSV);
+
+        let (cp1252, _, _) = WINDOWS_1252.encode(CSV);
+        assert_eq!(decode(cp1252.into_owned()), CSV);
+
+        assert_eq!(decode(CSV.as_bytes().to_vec()), CSV);
+    }
+
+    #[test]
+    fn test_detect_encoding() {
+        // 大部分是合法的 UTF-8 时, 个别坏字节不会让整个文件按 Windows-1252 转码
+        assert_eq!(detect_encoding(b"a,b\n1,H\xc3\xb6wedes\n4,\xff\n"), None);
+        assert_eq!(detect_encoding(b"a,b\n1,H\xf6wedes\n"), Some(WINDOWS_1252));
+
+        let text = "姓名,国籍\n布冯,意大利\n武磊,中国\n";
+        let utf16le: Vec<u8> = text.encode_utf16().flat_map(u16::to_le_bytes).collect();
+        assert_eq!(detect_encoding(&utf16le), Some(UTF_16LE));
+        let utf16be: Vec<u8> = text.encode_utf16().flat_map(u16::to_be_bytes).collect();
+        assert_eq!(detect_encoding(&utf16be), Some(UTF_16BE));
+    }
+}
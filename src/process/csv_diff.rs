@@ -0,0 +1,289 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Write as _,
+    io::Write,
+};
+
+use anyhow::{anyhow, Result};
+use csv::StringRecord;
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use super::{csv_convert::CsvRows, csv_ops::column_indices};
+use crate::{
+    cli::{CsvFilterOpts, CsvReaderOpts, OutputFormat},
+    get_writer,
+};
+
+#[derive(Debug, Default, Serialize)]
+struct CsvDiff {
+    columns: ColumnDiff,
+    added: Vec<Map<String, Value>>,
+    removed: Vec<Map<String, Value>>,
+    modified: Vec<RowDiff>,
+}
+
+#[derive(Debug, Default, Serialize)]
+struct ColumnDiff {
+    added: Vec<String>,
+    removed: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct RowDiff {
+    key: Map<String, Value>,
+    changes: Vec<CellChange>,
+}
+
+#[derive(Debug, Serialize)]
+struct CellChange {
+    column: String,
+    old: String,
+    new: String,
+}
+
+pub fn process_csv_diff(
+    old: &str,
+    new: &str,
+    output: &str,
+    format: Option<OutputFormat>,
+    key: &[String],
+    opts: &CsvReaderOpts,
+) -> Result<()> {
+    let filter = CsvFilterOpts::default();
+    let diff = diff_rows(
+        CsvRows::open(old, opts, &filter)?,
+        CsvRows::open(new, opts, &filter)?,
+        key,
+    )?;
+    let mut writer = get_writer(output)?;
+    match format {
+        None => write!(writer, "{}", diff.render(old, new))?,
+        Some(OutputFormat::Json) => {
+            serde_json::to_writer_pretty(&mut writer, &diff)?;
+            writeln!(writer)?;
+        }
+        Some(OutputFormat::Yaml) => serde_yaml::to_writer(&mut writer, &diff)?,
+        Some(format) => return Err(anyhow!("Unsupported diff format: {}", format)),
+    }
+    Ok(())
+}
+
+// 旧文件整个放进以键为索引的哈希表, 新文件逐行比较
+fn diff_rows(mut old: CsvRows, mut new: CsvRows, key: &[String]) -> Result<CsvDiff> {
+    if key.is_empty() {
+        return Err(anyhow!("At least one --key column is required"));
+    }
+    let old_headers = old.headers().clone();
+    let new_headers = new.headers().clone();
+    let old_keys = column_indices(&old_headers, key).map_err(|e| anyhow!("old file: {}", e))?;
+    let new_keys = column_indices(&new_headers, key).map_err(|e| anyhow!("new file: {}", e))?;
+
+    let mut diff = CsvDiff::default();
+    diff.columns.added = missing_from(&new_headers, &old_headers);
+    diff.columns.removed = missing_from(&old_headers, &new_headers);
+    // 两边都有的列: (名称, 旧下标, 新下标)
+    let common: Vec<(&str, usize, usize)> = old_headers
+        .iter()
+        .enumerate()
+        .filter_map(|(i, name)| {
+            let j = new_headers.iter().position(|h| h == name)?;
+            Some((name, i, j))
+        })
+        .collect();
+
+    let mut index = HashMap::new();
+    let mut records = Vec::new();
+    while let Some(record) = old.next() {
+        let record = record?;
+        let k = row_key(&record, &old_keys);
+        if index.insert(k.clone(), records.len()).is_some() {
+            return Err(anyhow!(
+                "old file: duplicate key {:?} at line {}",
+                k,
+                old.line()
+            ));
+        }
+        records.push(record);
+    }
+
+    let mut seen = vec![false; records.len()];
+    let mut new_seen = HashSet::new();
+    while let Some(record) = new.next() {
+        let record = record?;
+        let k = row_key(&record, &new_keys);
+        if !new_seen.insert(k.clone()) {
+            return Err(anyhow!(
+                "new file: duplicate key {:?} at line {}",
+                k,
+                new.line()
+            ));
+        }
+        let Some(&i) = index.get(&k) else {
+            diff.added.push(row_object(&new_headers, &record));
+            continue;
+        };
+        seen[i] = true;
+        let changes: Vec<CellChange> = common
+            .iter()
+            .filter_map(|&(name, oi, ni)| {
+                let (o, n) = (records[i].get(oi)?, record.get(ni)?);
+                (o != n).then(|| CellChange {
+                    column: name.to_string(),
+                    old: o.to_string(),
+                    new: n.to_string(),
+                })
+            })
+            .collect();
+        if !changes.is_empty() {
+            diff.modified.push(RowDiff {
+                key: key
+                    .iter()
+                    .cloned()
+                    .zip(k.into_iter().map(Value::String))
+                    .collect(),
+                changes,
+            });
+        }
+    }
+
+    diff.removed = records
+        .iter()
+        .zip(&seen)
+        .filter(|(_, s)| !**s)
+        .map(|(record, _)| row_object(&old_headers, record))
+        .collect();
+    Ok(diff)
+}
+
+impl CsvDiff {
+    // 类似 diff -u 的文本输出: + 新增, - 删除, ~ 修改
+    fn render(&self, old: &str, new: &str) -> String {
+        let mut out = String::new();
+        let _ = writeln!(out, "--- {}\n+++ {}", old, new);
+        for column in &self.columns.added {
+            let _ = writeln!(out, "+ column {}", column);
+        }
+        for column in &self.columns.removed {
+            let _ = writeln!(out, "- column {}", column);
+        }
+        for row in &self.removed {
+            let _ = writeln!(out, "- {}", describe(row));
+        }
+        for row in &self.added {
+            let _ = writeln!(out, "+ {}", describe(row));
+        }
+        for row in &self.modified {
+            let _ = writeln!(out, "~ {}", describe(&row.key));
+            for change in &row.changes {
+                let _ = writeln!(
+                    out,
+                    "    {}: {:?} -> {:?}",
+                    change.column, change.old, change.new
+                );
+            }
+        }
+        let _ = writeln!(
+            out,
+            "{} added, {} removed, {} modified",
+            self.added.len(),
+            self.removed.len(),
+            self.modified.len()
+        );
+        out
+    }
+}
+
+fn missing_from(headers: &StringRecord, other: &StringRecord) -> Vec<String> {
+    headers
+        .iter()
+        .filter(|h| !other.iter().any(|o| o == *h))
+        .map(String::from)
+        .collect()
+}
+
+fn row_key(record: &StringRecord, keys: &[usize]) -> Vec<String> {
+    keys.iter()
+        .map(|&i| record.get(i).unwrap_or("").to_string())
+        .collect()
+}
+
+fn row_object(headers: &StringRecord, record: &StringRecord) -> Map<String, Value> {
+    headers
+        .iter()
+        .zip(record.iter())
+        .map(|(h, v)| (h.to_string(), Value::String(v.to_string())))
+        .collect()
+}
+
+fn describe(row: &Map<String, Value>) -> String {
+    row.iter()
+        .map(|(k, v)| format!("{}={}", k, v.as_str().unwrap_or_default()))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{super::test_utils::temp_file, *};
+    use tempfile::NamedTempFile;
+
+    fn rows(content: &str) -> (NamedTempFile, CsvRows) {
+        let file = temp_file(content);
+        let opts = CsvReaderOpts::default();
+        let rows = CsvRows::open(
+            file.path().to_str().unwrap(),
+            &opts,
+            &CsvFilterOpts::default(),
+        )
+        .unwrap();
+        (file, rows)
+    }
+
+    const OLD: &str = "Name,Position,Kit Number\n\
+        Paulo Dybala,Second Striker,10\n\
+        Mattia Perin,Goalkeeper,37\n\
+        Sami Khedira,Central Midfield,6\n";
+
+    const NEW: &str = "Name,Kit Number,Goals\n\
+        Sami Khedira,6,2\n\
+        Paulo Dybala,21,11\n\
+        Emre Can,23,1\n";
+
+    #[test]
+    fn test_diff_rows() {
+        let (_old, old) = rows(OLD);
+        let (_new, new) = rows(NEW);
+        let diff = diff_rows(old, new, &["Name".to_string()]).unwrap();
+        assert_eq!(
+            diff.render("old.csv", "new.csv"),
+            "--- old.csv\n\
+             +++ new.csv\n\
+             + column Goals\n\
+             - column Position\n\
+             - Name=Mattia Perin, Position=Goalkeeper, Kit Number=37\n\
+             + Name=Emre Can, Kit Number=23, Goals=1\n\
+             ~ Name=Paulo Dybala\n    \
+             Kit Number: \"10\" -> \"21\"\n\
+             1 added, 1 removed, 1 modified\n"
+        );
+        let json = serde_json::to_value(&diff).unwrap();
+        assert_eq!(
+            json["modified"][0]["changes"][0],
+            serde_json::json!({"column": "Kit Number", "old": "10", "new": "21"})
+        );
+    }
+
+    #[test]
+    fn test_diff_rows_identical_and_duplicates() {
+        let (_old, old) = rows(OLD);
+        let (_new, new) = rows(OLD);
+        let diff = diff_rows(old, new, &["Name".to_string()]).unwrap();
+        assert!(diff.added.is_empty() && diff.removed.is_empty() && diff.modified.is_empty());
+
+        let (_old, old) = rows("Name\nBuffon\nBuffon\n");
+        let (_new, new) = rows("Name\nBuffon\n");
+        let err = diff_rows(old, new, &["Name".to_string()]).unwrap_err();
+        assert!(err.to_string().contains("duplicate key"));
+    }
+}
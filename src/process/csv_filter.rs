@@ -0,0 +1,484 @@
+use anyhow::{anyhow, Result};
+use csv::StringRecord;
+use regex::Regex;
+
+// `--select Name,Position`: 按给定顺序投影列
+pub(crate) struct Projection {
+    indices: Vec<usize>,
+}
+
+// `--where "Nationality == 'Italy' && Kit Number > 10"` 解析后的表达式树
+pub(crate) struct RowFilter {
+    expr: Expr,
+}
+
+#[derive(Debug)]
+enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Compare(usize, CmpOp, Literal),
+    Contains(usize, String),
+    StartsWith(usize, String),
+    EndsWith(usize, String),
+    Matches(usize, Regex),
+    IsNull(usize),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum CmpOp {
+    Eq,
+    Ne,
+    Gt,
+    Ge,
+    Lt,
+    Le,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Literal {
+    Number(f64),
+    String(String),
+    Bool(bool),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    Column(String),
+    Str(String),
+    Number(f64),
+    Op(CmpOp),
+    And,
+    Or,
+    Not,
+    LParen,
+    RParen,
+}
+
+const KEYWORDS: &[&str] = &[
+    "is",
+    "null",
+    "true",
+    "false",
+    "contains",
+    "matches",
+    "startswith",
+    "endswith",
+];
+
+impl Projection {
+    pub(crate) fn new(headers: &StringRecord, columns: &[String]) -> Result<Self> {
+        let indices = columns
+            .iter()
+            .map(|name| {
+                headers
+                    .iter()
+                    .position(|h| h == name)
+                    .ok_or_else(|| anyhow!("Unknown column in --select: {}", name))
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self { indices })
+    }
+
+    pub(crate) fn apply(&self, record: &StringRecord) -> StringRecord {
+        self.indices
+            .iter()
+            .map(|&i| record.get(i).unwrap_or(""))
+            .collect()
+    }
+}
+
+impl RowFilter {
+    pub(crate) fn parse(input: &str, headers: &StringRecord) -> Result<Self> {
+        let tokens = tokenize(input)?;
+        let mut parser = Parser {
+            tokens,
+            pos: 0,
+            headers,
+            len: input.chars().count(),
+        };
+        let expr = parser.parse_or()?;
+        if let Some((_, col)) = parser.tokens.get(parser.pos) {
+            return Err(anyhow!("Parse error at column {}: unexpected token", col));
+        }
+        Ok(Self { expr })
+    }
+
+    pub(crate) fn matches(&self, record: &StringRecord) -> bool {
+        self.expr.eval(record)
+    }
+}
+
+impl Expr {
+    fn eval(&self, record: &StringRecord) -> bool {
+        let cell = |idx: &usize| record.get(*idx).unwrap_or("");
+        match self {
+            Expr::And(l, r) => l.eval(record) && r.eval(record),
+            Expr::Or(l, r) => l.eval(record) || r.eval(record),
+            Expr::Not(e) => !e.eval(record),
+            Expr::Compare(idx, op, lit) => compare(cell(idx), *op, lit),
+            Expr::Contains(idx, s) => cell(idx).contains(s.as_str()),
+            Expr::StartsWith(idx, s) => cell(idx).starts_with(s.as_str()),
+            Expr::EndsWith(idx, s) => cell(idx).ends_with(s.as_str()),
+            Expr::Matches(idx, re) => re.is_match(cell(idx)),
+            Expr::IsNull(idx) => cell(idx).is_empty(),
+        }
+    }
+}
+
+// 单元格按照字面量的类型去解析, 解析失败 (包括空值) 视为不匹配
+fn compare(cell: &str, op: CmpOp, lit: &Literal) -> bool {
+    let ord = match lit {
+        Literal::Number(n) => match cell.trim().parse::<f64>() {
+            Ok(v) => v.partial_cmp(n),
+            Err(_) => None,
+        },
+        Literal::Bool(b) => match cell.trim().to_lowercase().as_str() {
+            "true" => Some(true.cmp(b)),
+            "false" => Some(false.cmp(b)),
+            _ => None,
+        },
+        Literal::String(s) => Some(cell.cmp(s.as_str())),
+    };
+    let Some(ord) = ord else {
+        return op == CmpOp::Ne;
+    };
+    match op {
+        CmpOp::Eq => ord.is_eq(),
+        CmpOp::Ne => ord.is_ne(),
+        CmpOp::Gt => ord.is_gt(),
+        CmpOp::Ge => ord.is_ge(),
+        CmpOp::Lt => ord.is_lt(),
+        CmpOp::Le => ord.is_le(),
+    }
+}
+
+const OPERATORS: &[(&str, Token)] = &[
+    ("&&", Token::And),
+    ("||", Token::Or),
+    ("==", Token::Op(CmpOp::Eq)),
+    ("!=", Token::Op(CmpOp::Ne)),
+    (">=", Token::Op(CmpOp::Ge)),
+    ("<=", Token::Op(CmpOp::Le)),
+    ("=", Token::Op(CmpOp::Eq)),
+    (">", Token::Op(CmpOp::Gt)),
+    ("<", Token::Op(CmpOp::Lt)),
+    ("!", Token::Not),
+    ("(", Token::LParen),
+    (")", Token::RParen),
+];
+
+// 返回 token 以及它在表达式中的列号 (从 1 开始)
+fn tokenize(input: &str) -> Result<Vec<(Token, usize)>> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let col = i + 1;
+        let rest: String = chars[i..chars.len().min(i + 2)].iter().collect();
+        if c.is_whitespace() {
+            i += 1;
+        } else if matches!(c, '\'' | '"' | '`') {
+            let end = chars[i + 1..]
+                .iter()
+                .position(|&x| x == c)
+                .ok_or_else(|| anyhow!("Parse error at column {}: unterminated quote", col))?;
+            let s: String = chars[i + 1..i + 1 + end].iter().collect();
+            // 反引号引用列名, 用于包含关键字或特殊字符的列
+            let token = if c == '`' {
+                Token::Column(s)
+            } else {
+                Token::Str(s)
+            };
+            tokens.push((token, col));
+            i += end + 2;
+        } else if c.is_ascii_digit()
+            || (c == '-' && chars.get(i + 1).is_some_and(char::is_ascii_digit))
+        {
+            let start = i;
+            i += 1;
+            while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                i += 1;
+            }
+            let s: String = chars[start..i].iter().collect();
+            let n = s
+                .parse()
+                .map_err(|_| anyhow!("Parse error at column {}: invalid number {}", col, s))?;
+            tokens.push((Token::Number(n), col));
+        } else if c.is_alphanumeric() || c == '_' {
+            let start = i;
+            while i < chars.len()
+                && (chars[i].is_alphanumeric() || matches!(chars[i], '_' | '.' | '-'))
+            {
+                i += 1;
+            }
+            let word: String = chars[start..i].iter().collect();
+            let token = match word.to_lowercase().as_str() {
+                "and" => Token::And,
+                "or" => Token::Or,
+                "not" => Token::Not,
+                _ => Token::Word(word),
+            };
+            tokens.push((token, col));
+        } else if let Some((op, token)) = OPERATORS.iter().find(|(op, _)| rest.starts_with(op)) {
+            tokens.push((token.clone(), col));
+            i += op.len();
+        } else {
+            return Err(anyhow!("Parse error at column {}: unexpected '{}'", col, c));
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser<'a> {
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+    headers: &'a StringRecord,
+    len: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(t, _)| t)
+    }
+
+    // 当前 token 的列号, 到结尾时指向表达式末尾
+    fn col(&self) -> usize {
+        self.tokens.get(self.pos).map_or(self.len + 1, |(_, c)| *c)
+    }
+
+    fn error(&self, msg: &str) -> anyhow::Error {
+        anyhow!("Parse error at column {}: {}", self.col(), msg)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).map(|(t, _)| t.clone());
+        self.pos += 1;
+        token
+    }
+
+    fn is_keyword(&self, word: &str) -> bool {
+        matches!(self.peek(), Some(Token::Word(w)) if w.eq_ignore_ascii_case(word))
+    }
+
+    fn parse_or(&mut self) -> Result<Expr> {
+        let mut left = self.parse_and()?;
+        while self.peek() == Some(&Token::Or) {
+            self.pos += 1;
+            left = Expr::Or(Box::new(left), Box::new(self.parse_and()?));
+        }
+        Ok(left)
+    }
+
+    fn parse_and(&mut self) -> Result<Expr> {
+        let mut left = self.parse_unary()?;
+        while self.peek() == Some(&Token::And) {
+            self.pos += 1;
+            left = Expr::And(Box::new(left), Box::new(self.parse_unary()?));
+        }
+        Ok(left)
+    }
+
+    fn parse_unary(&mut self) -> Result<Expr> {
+        match self.peek() {
+            Some(Token::Not) => {
+                self.pos += 1;
+                Ok(Expr::Not(Box::new(self.parse_unary()?)))
+            }
+            Some(Token::LParen) => {
+                self.pos += 1;
+                let expr = self.parse_or()?;
+                if self.next() != Some(Token::RParen) {
+                    self.pos -= 1;
+                    return Err(self.error("expected ')'"));
+                }
+                Ok(expr)
+            }
+            _ => self.parse_predicate(),
+        }
+    }
+
+    fn parse_predicate(&mut self) -> Result<Expr> {
+        let idx = self.parse_column()?;
+        if self.is_keyword("is") {
+            self.pos += 1;
+            let negate = self.peek() == Some(&Token::Not);
+            if negate {
+                self.pos += 1;
+            }
+            if !self.is_keyword("null") {
+                return Err(self.error("expected 'null'"));
+            }
+            self.pos += 1;
+            let expr = Expr::IsNull(idx);
+            return Ok(if negate {
+                Expr::Not(Box::new(expr))
+            } else {
+                expr
+            });
+        }
+        for keyword in ["contains", "startswith", "endswith", "matches"] {
+            if self.is_keyword(keyword) {
+                self.pos += 1;
+                let col = self.col();
+                let Some(Token::Str(s)) = self.next() else {
+                    self.pos -= 1;
+                    return Err(self.error("expected a quoted string"));
+                };
+                return Ok(match keyword {
+                    "contains" => Expr::Contains(idx, s),
+                    "startswith" => Expr::StartsWith(idx, s),
+                    "endswith" => Expr::EndsWith(idx, s),
+                    _ => Expr::Matches(
+                        idx,
+                        Regex::new(&s).map_err(|e| {
+                            anyhow!("Parse error at column {}: invalid regex: {}", col, e)
+                        })?,
+                    ),
+                });
+            }
+        }
+        let op = match self.next() {
+            Some(Token::Op(op)) => op,
+            _ => {
+                self.pos -= 1;
+                return Err(self.error("expected an operator"));
+            }
+        };
+        let literal = match self.next() {
+            Some(Token::Number(n)) => Literal::Number(n),
+            Some(Token::Str(s)) => Literal::String(s),
+            Some(Token::Word(w)) if w.eq_ignore_ascii_case("true") => Literal::Bool(true),
+            Some(Token::Word(w)) if w.eq_ignore_ascii_case("false") => Literal::Bool(false),
+            Some(Token::Word(w)) if w.eq_ignore_ascii_case("null") => {
+                let expr = Expr::IsNull(idx);
+                return Ok(match op {
+                    CmpOp::Eq => expr,
+                    CmpOp::Ne => Expr::Not(Box::new(expr)),
+                    _ => {
+                        // 指向比较运算符
+                        self.pos -= 2;
+                        return Err(self.error("null only supports == and !="));
+                    }
+                });
+            }
+            _ => {
+                self.pos -= 1;
+                return Err(self.error("expected a value"));
+            }
+        };
+        Ok(Expr::Compare(idx, op, literal))
+    }
+
+    // 列名可以不加引号并包含空格, 比如 `Kit Number > 10`, 连续的单词会被拼成一个列名
+    fn parse_column(&mut self) -> Result<usize> {
+        let col = self.col();
+        let name = match self.peek() {
+            Some(Token::Column(name)) => {
+                let name = name.clone();
+                self.pos += 1;
+                name
+            }
+            Some(Token::Word(_)) => {
+                let mut words = Vec::new();
+                while let Some(Token::Word(w)) = self.peek() {
+                    if !words.is_empty() && KEYWORDS.contains(&w.to_lowercase().as_str()) {
+                        break;
+                    }
+                    words.push(w.clone());
+                    self.pos += 1;
+                }
+                words.join(" ")
+            }
+            _ => return Err(self.error("expected a column name")),
+        };
+        self.headers
+            .iter()
+            .position(|h| h == name)
+            .ok_or_else(|| anyhow!("Unknown column '{}' at column {}", name, col))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers() -> StringRecord {
+        StringRecord::from(vec!["Name", "Position", "Nationality", "Kit Number"])
+    }
+
+    fn matches(expr: &str, record: Vec<&str>) -> bool {
+        let filter = RowFilter::parse(expr, &headers()).unwrap();
+        filter.matches(&StringRecord::from(record))
+    }
+
+    #[test]
+    fn test_filter_comparison_and_logic() {
+        let buffon = vec!["Gianluigi Buffon", "Goalkeeper", "Italy", "77"];
+        let szczesny = vec!["Wojciech Szczesny", "Goalkeeper", "Poland", "1"];
+        let expr = "Nationality == 'Italy' && Kit Number > 10";
+        assert!(matches(expr, buffon.clone()));
+        assert!(!matches(expr, szczesny.clone()));
+        assert!(matches(
+            "Nationality = 'Italy' or `Kit Number` <= 1",
+            szczesny.clone()
+        ));
+        assert!(matches("!(Kit Number >= 10)", szczesny.clone()));
+        assert!(matches("not Nationality != \"Poland\"", szczesny));
+    }
+
+    #[test]
+    fn test_filter_string_matching_and_null() {
+        let row = vec!["Gianluigi Buffon", "Goalkeeper", "", "77"];
+        assert!(matches("Name contains 'Buffon'", row.clone()));
+        assert!(matches(
+            "Name startswith 'Gian' and Name endswith 'fon'",
+            row.clone()
+        ));
+        assert!(matches("Name matches '^G.*n$'", row.clone()));
+        assert!(matches("Nationality is null", row.clone()));
+        assert!(matches("Kit Number is not null", row.clone()));
+        assert!(matches("Nationality == null", row.clone()));
+        // 空值不参与数值比较
+        assert!(!matches("Nationality > 1", row));
+    }
+
+    #[test]
+    fn test_filter_parse_errors() {
+        let err = |expr: &str| {
+            RowFilter::parse(expr, &headers())
+                .err()
+                .unwrap()
+                .to_string()
+        };
+        assert_eq!(
+            err("Nationality == "),
+            "Parse error at column 16: expected a value"
+        );
+        assert_eq!(err("Age > 10"), "Unknown column 'Age' at column 1");
+        assert_eq!(
+            err("Name == 'x' && (Kit Number > 1"),
+            "Parse error at column 31: expected ')'"
+        );
+        assert_eq!(
+            err("Name == 'x"),
+            "Parse error at column 9: unterminated quote"
+        );
+        assert!(err("Name matches '('").starts_with("Parse error at column 14: invalid regex"));
+        assert_eq!(
+            err("Name > null"),
+            "Parse error at column 6: null only supports == and !="
+        );
+    }
+
+    #[test]
+    fn test_projection() {
+        let projection =
+            Projection::new(&headers(), &["Kit Number".into(), "Name".into()]).unwrap();
+        let record = StringRecord::from(vec!["Buffon", "Goalkeeper", "Italy", "77"]);
+        assert_eq!(projection.apply(&record), vec!["77", "Buffon"]);
+        assert!(Projection::new(&headers(), &["Age".into()]).is_err());
+    }
+}
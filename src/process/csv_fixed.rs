@@ -0,0 +1,230 @@
+use std::{
+    fs,
+    io::{self, BufRead, BufReader, Read},
+};
+
+use anyhow::{anyhow, Result};
+use csv::Writer;
+use serde::Deserialize;
+
+use crate::cli::CsvType;
+
+// 定宽文件的列定义, 例如 assets/accounts.fixed.yaml
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct FixedWidthSpec {
+    // 跳过开头的若干行, 比如报表的标题
+    #[serde(default)]
+    skip: usize,
+    columns: Vec<FixedColumn>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FixedColumn {
+    name: String,
+    // 从 1 开始, 按字符而不是字节计算
+    start: usize,
+    width: usize,
+    #[serde(rename = "type")]
+    ty: Option<String>,
+    #[serde(default)]
+    trim: FixedTrim,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum FixedTrim {
+    #[default]
+    Both,
+    Left,
+    Right,
+    None,
+}
+
+// 把定宽的行逐行转换成 csv, 第一行是列名
+pub(crate) struct FixedWidthReader {
+    lines: io::Lines<BufReader<Box<dyn Read>>>,
+    columns: Vec<FixedColumn>,
+    skip: usize,
+    buf: Vec<u8>,
+    pos: usize,
+}
+
+impl FixedWidthSpec {
+    pub(crate) fn load(path: &str) -> Result<Self> {
+        let spec: Self = serde_yaml::from_str(&fs::read_to_string(path)?)
+            .map_err(|e| anyhow!("Invalid fixed-width spec {}: {}", path, e))?;
+        if spec.columns.is_empty() {
+            return Err(anyhow!("Fixed-width spec {} has no columns", path));
+        }
+        for c in &spec.columns {
+            if c.start == 0 || c.width == 0 {
+                return Err(anyhow!(
+                    "Column '{}': start and width must be at least 1",
+                    c.name
+                ));
+            }
+        }
+        spec.types()?;
+        Ok(spec)
+    }
+
+    pub(crate) fn skip(&self) -> usize {
+        self.skip
+    }
+
+    // 列定义中声明的类型, 交给 CellTyper 转换
+    pub(crate) fn types(&self) -> Result<Vec<(String, CsvType)>> {
+        self.columns
+            .iter()
+            .filter_map(|c| c.ty.as_ref().map(|ty| (c, ty)))
+            .map(|(c, ty)| {
+                let ty = ty
+                    .parse()
+                    .map_err(|e| anyhow!("Column '{}': {}", c.name, e))?;
+                Ok((c.name.clone(), ty))
+            })
+            .collect()
+    }
+
+    pub(crate) fn reader(self, input: Box<dyn Read>) -> Result<FixedWidthReader> {
+        Ok(FixedWidthReader {
+            lines: BufReader::new(input).lines(),
+            buf: csv_line(self.columns.iter().map(|c| c.name.as_str()))?,
+            columns: self.columns,
+            skip: self.skip,
+            pos: 0,
+        })
+    }
+}
+
+impl FixedWidthReader {
+    // 读入下一行并转换到 buf 中, 输入结束时返回 false
+    fn fill(&mut self) -> io::Result<bool> {
+        let line = loop {
+            match self.lines.next() {
+                None => return Ok(false),
+                Some(line) if self.skip > 0 => {
+                    line?;
+                    self.skip -= 1;
+                }
+                Some(line) => break line?,
+            }
+        };
+        let line = line.strip_suffix('\r').unwrap_or(&line);
+        if line.trim().is_empty() {
+            // 空行保留为空行, csv reader 会跳过它
+            self.buf = b"\n".to_vec();
+        } else {
+            let fields: Vec<String> = self.columns.iter().map(|c| c.slice(line)).collect();
+            self.buf = csv_line(fields.iter().map(String::as_str)).map_err(io::Error::other)?;
+        }
+        self.pos = 0;
+        Ok(true)
+    }
+}
+
+impl Read for FixedWidthReader {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        while self.pos >= self.buf.len() {
+            if !self.fill()? {
+                return Ok(0);
+            }
+        }
+        let n = out.len().min(self.buf.len() - self.pos);
+        out[..n].copy_from_slice(&self.buf[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+fn csv_line<'a>(fields: impl Iterator<Item = &'a str>) -> Result<Vec<u8>> {
+    let mut writer = Writer::from_writer(Vec::new());
+    writer.write_record(fields)?;
+    writer
+        .into_inner()
+        .map_err(|e| anyhow!("Failed to write csv line: {}", e))
+}
+
+impl FixedColumn {
+    // 行比列定义短时, 缺少的部分当作空
+    fn slice(&self, line: &str) -> String {
+        let cell: String = line.chars().skip(self.start - 1).take(self.width).collect();
+        match self.trim {
+            FixedTrim::Both => cell.trim().to_string(),
+            FixedTrim::Left => cell.trim_start().to_string(),
+            FixedTrim::Right => cell.trim_end().to_string(),
+            FixedTrim::None => cell,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    const SPEC: &str = r#"
+skip: 1
+columns:
+  - name: account
+    start: 1
+    width: 8
+    trim: none
+  - name: owner
+    start: 9
+    width: 12
+  - name: balance
+    start: 21
+    width: 10
+    type: float
+  - name: active
+    start: 31
+    width: 1
+    type: bool
+"#;
+
+    #[test]
+    fn test_fixed_width_reader() {
+        let spec: FixedWidthSpec = serde_yaml::from_str(SPEC).unwrap();
+        assert_eq!(
+            spec.types().unwrap(),
+            vec![
+                ("balance".to_string(), CsvType::Float),
+                ("active".to_string(), CsvType::Boolean)
+            ]
+        );
+        let input = "ACCOUNTS REPORT 2019-07-01\r\n\
+                     00012345Gianluigi B.   1024.50Y\r\n\
+                     \r\n\
+                     00067890Paulo Dybala-0000012.5N\n\
+                     00011111Höwedes";
+        let mut out = String::new();
+        spec.reader(Box::new(Cursor::new(input.to_string())))
+            .unwrap()
+            .read_to_string(&mut out)
+            .unwrap();
+        assert_eq!(
+            out,
+            "account,owner,balance,active\n\
+             00012345,Gianluigi B.,1024.50,Y\n\
+             \n\
+             00067890,Paulo Dybala,-0000012.5,N\n\
+             00011111,Höwedes,,\n"
+        );
+    }
+
+    #[test]
+    fn test_fixed_width_spec_errors() {
+        let spec = |s: &str| -> Result<FixedWidthSpec> {
+            let spec: FixedWidthSpec = serde_yaml::from_str(s)?;
+            spec.types()?;
+            Ok(spec)
+        };
+        assert!(spec("columns:\n  - {name: a, start: 1, width: 2, type: date}").is_err());
+        assert!(spec("columns:\n  - {name: a, start: 1, width: 2, align: left}").is_err());
+        assert!(spec("columns:\n  - {name: a, start: 1, width: 2, trim: middle}").is_err());
+    }
+}
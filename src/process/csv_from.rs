@@ -0,0 +1,159 @@
+use std::collections::{HashMap, HashSet};
+
+use anyhow::{anyhow, Result};
+use csv::WriterBuilder;
+use serde_json::{Map, Value};
+
+use crate::{cli::OutputFormat, get_reader, get_writer};
+
+pub fn process_csv_from(
+    input: &str,
+    output: &str,
+    format: OutputFormat,
+    delimiter: u8,
+) -> Result<()> {
+    let mut reader = get_reader(input)?;
+    let mut buf = String::new();
+    reader.read_to_string(&mut buf)?;
+
+    let value: Value = match format {
+        OutputFormat::Json => serde_json::from_str(&buf)?,
+        OutputFormat::Ndjson => buf
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(serde_json::from_str::<Value>)
+            .collect::<Result<_, _>>()?,
+        OutputFormat::Yaml => serde_yaml::from_str(&buf)?,
+        OutputFormat::Toml => toml::from_str(&buf)?,
+        format => return Err(anyhow!("Cannot convert {} back to CSV", format)),
+    };
+    let rows = flatten_records(value)?;
+    let headers = union_headers(&rows);
+
+    let mut writer = WriterBuilder::new()
+        .delimiter(delimiter)
+        .from_writer(get_writer(output)?);
+    writer.write_record(&headers)?;
+    for row in &rows {
+        let row: HashMap<&str, &str> = row.iter().map(|(k, v)| (k.as_str(), v.as_str())).collect();
+        writer.write_record(headers.iter().map(|h| row.get(h.as_str()).unwrap_or(&"")))?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+// 把输入解析成若干条扁平化的记录, 每条记录保持字段出现的顺序
+fn flatten_records(value: Value) -> Result<Vec<Vec<(String, String)>>> {
+    let records = match value {
+        Value::Array(arr) => arr,
+        // process_csv 输出 TOML 时会包一层 `a = [...]`, 同理也接受 `{"data": [...]}`
+        Value::Object(map) if map.len() == 1 && map.values().all(Value::is_array) => {
+            match map.into_iter().next() {
+                Some((_, Value::Array(arr))) => arr,
+                _ => unreachable!("checked above"),
+            }
+        }
+        Value::Object(map) => vec![Value::Object(map)],
+        _ => return Err(anyhow!("Expected an array of objects")),
+    };
+
+    records
+        .into_iter()
+        .enumerate()
+        .map(|(i, record)| match record {
+            Value::Object(map) => {
+                let mut row = Vec::with_capacity(map.len());
+                flatten_object("", &map, &mut row);
+                Ok(row)
+            }
+            _ => Err(anyhow!("Record {} is not an object", i + 1)),
+        })
+        .collect()
+}
+
+// {"address": {"city": "Turin"}, "tags": ["a"]} => address.city, tags[0]
+fn flatten_object(prefix: &str, map: &Map<String, Value>, row: &mut Vec<(String, String)>) {
+    for (key, value) in map {
+        let name = if prefix.is_empty() {
+            key.clone()
+        } else {
+            format!("{}.{}", prefix, key)
+        };
+        flatten_value(name, value, row);
+    }
+}
+
+fn flatten_value(name: String, value: &Value, row: &mut Vec<(String, String)>) {
+    match value {
+        Value::Object(map) if !map.is_empty() => flatten_object(&name, map, row),
+        Value::Array(arr) if !arr.is_empty() => {
+            for (i, v) in arr.iter().enumerate() {
+                flatten_value(format!("{}[{}]", name, i), v, row);
+            }
+        }
+        Value::Null => row.push((name, String::new())),
+        Value::String(s) => row.push((name, s.clone())),
+        // 数字, 布尔以及空的数组/对象直接使用 JSON 文本
+        v => row.push((name, v.to_string())),
+    }
+}
+
+// 记录字段不一致时取并集, 按第一次出现的顺序排列
+fn union_headers(rows: &[Vec<(String, String)>]) -> Vec<String> {
+    let mut seen = HashSet::new();
+    let mut headers = Vec::new();
+    for (name, _) in rows.iter().flatten() {
+        if seen.insert(name.as_str()) {
+            headers.push(name.clone());
+        }
+    }
+    headers
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn get<'a>(row: &'a [(String, String)], name: &str) -> Option<&'a str> {
+        row.iter().find(|(k, _)| k == name).map(|(_, v)| v.as_str())
+    }
+
+    #[test]
+    fn test_flatten_nested_records() {
+        let value = json!([
+            {"name": "Buffon", "address": {"city": "Turin", "zip": 10121}, "tags": ["gk", "captain"]},
+            {"name": "Perin", "kit": 37, "address": {"city": null}}
+        ]);
+        let rows = flatten_records(value).unwrap();
+        assert_eq!(
+            union_headers(&rows),
+            vec![
+                "name",
+                "address.city",
+                "address.zip",
+                "tags[0]",
+                "tags[1]",
+                "kit"
+            ]
+        );
+        assert_eq!(get(&rows[0], "address.zip").unwrap(), "10121");
+        assert_eq!(get(&rows[1], "address.city").unwrap(), "");
+        assert!(get(&rows[1], "tags[0]").is_none());
+    }
+
+    #[test]
+    fn test_flatten_toml_wrapper() {
+        let value: Value =
+            toml::from_str("[[a]]\nName = \"Buffon\"\n\n[[a]]\nName = \"Perin\"\n").unwrap();
+        let rows = flatten_records(value).unwrap();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(get(&rows[1], "Name").unwrap(), "Perin");
+    }
+
+    #[test]
+    fn test_flatten_rejects_scalars() {
+        assert!(flatten_records(json!([1, 2])).is_err());
+        assert!(flatten_records(json!("x")).is_err());
+    }
+}
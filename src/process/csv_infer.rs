@@ -0,0 +1,196 @@
+use anyhow::{anyhow, Result};
+use csv::StringRecord;
+use serde_json::{Map, Number, Value};
+
+use crate::cli::{CsvType, CsvTypeOpts};
+
+// 按列把单元格转换成带类型的 Value, --types 指定的列优先, 其余列按 --infer 推断
+pub(crate) struct CellTyper {
+    types: Vec<Option<CsvType>>,
+    infer: bool,
+}
+
+impl CellTyper {
+    pub(crate) fn new(headers: &StringRecord, opts: &CsvTypeOpts) -> Result<Self> {
+        let mut types = vec![None; headers.len()];
+        for (name, ty) in &opts.types {
+            let idx = headers
+                .iter()
+                .position(|h| h == name)
+                .ok_or_else(|| anyhow!("Unknown column in --types: {}", name))?;
+            types[idx] = Some(*ty);
+        }
+        Ok(Self {
+            types,
+            infer: opts.infer,
+        })
+    }
+
+    pub(crate) fn value(&self, idx: usize, cell: &str) -> Result<Value> {
+        match self.types.get(idx).copied().flatten() {
+            Some(ty) => convert_cell(cell, ty),
+            None if self.infer => Ok(infer_cell(cell)),
+            None => Ok(Value::String(cell.to_string())),
+        }
+    }
+
+    pub(crate) fn row(&self, headers: &StringRecord, record: &StringRecord) -> Result<Value> {
+        let mut map = Map::with_capacity(headers.len());
+        for (idx, (name, cell)) in headers.iter().zip(record.iter()).enumerate() {
+            let value = self
+                .value(idx, cell)
+                .map_err(|e| anyhow!("column '{}': {}", name, e))?;
+            map.insert(name.to_string(), value);
+        }
+        Ok(Value::Object(map))
+    }
+}
+
+pub(crate) fn infer_cell(cell: &str) -> Value {
+    if cell.is_empty() {
+        return Value::Null;
+    }
+    if cell.eq_ignore_ascii_case("true") {
+        return Value::Bool(true);
+    }
+    if cell.eq_ignore_ascii_case("false") {
+        return Value::Bool(false);
+    }
+    // "007" 这类编号保留成字符串, 否则会丢掉前导零
+    if !looks_numeric(cell) || has_leading_zero(cell) {
+        return Value::String(cell.to_string());
+    }
+    // 超出 i64 的整数 (比如账号) 转成 f64 会丢失精度, 保留成字符串; 需要浮点数时用 --types 指定 float
+    if !cell.contains('.') {
+        return match cell.parse::<i64>() {
+            Ok(i) => Value::Number(i.into()),
+            Err(_) => Value::String(cell.to_string()),
+        };
+    }
+    match cell.parse::<f64>().ok().and_then(Number::from_f64) {
+        Some(n) => Value::Number(n),
+        None => Value::String(cell.to_string()),
+    }
+}
+
+pub(crate) fn convert_cell(cell: &str, ty: CsvType) -> Result<Value> {
+    if cell.is_empty() && ty != CsvType::String {
+        return Ok(Value::Null);
+    }
+    let value = match ty {
+        CsvType::String => Value::String(cell.to_string()),
+        CsvType::Integer => Value::Number(
+            cell.parse::<i64>()
+                .map_err(|_| anyhow!("invalid integer: {}", cell))?
+                .into(),
+        ),
+        CsvType::Float => cell
+            .parse::<f64>()
+            .ok()
+            .and_then(Number::from_f64)
+            .map(Value::Number)
+            .ok_or_else(|| anyhow!("invalid float: {}", cell))?,
+        CsvType::Boolean => match cell.to_lowercase().as_str() {
+            "true" | "yes" | "y" | "1" => Value::Bool(true),
+            "false" | "no" | "n" | "0" => Value::Bool(false),
+            _ => return Err(anyhow!("invalid boolean: {}", cell)),
+        },
+    };
+    Ok(value)
+}
+
+// 排除 f64 能解析但不想当作数字的写法, 比如 "inf", "NaN", "1e5"
+fn looks_numeric(cell: &str) -> bool {
+    let digits = cell.strip_prefix('-').unwrap_or(cell);
+    !digits.is_empty()
+        && digits.bytes().all(|b| b.is_ascii_digit() || b == b'.')
+        && digits.bytes().filter(|b| *b == b'.').count() <= 1
+        && digits.bytes().any(|b| b.is_ascii_digit())
+}
+
+fn has_leading_zero(cell: &str) -> bool {
+    let digits = cell.strip_prefix('-').unwrap_or(cell);
+    digits.len() > 1 && digits.starts_with('0') && !digits.starts_with("0.")
+}
+
+// TOML 没有 null, 输出前需要去掉值为 null 的字段和数组元素
+pub(crate) fn drop_nulls(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            map.retain(|_, v| !v.is_null());
+            map.values_mut().for_each(drop_nulls);
+        }
+        Value::Array(arr) => {
+            arr.retain(|v| !v.is_null());
+            arr.iter_mut().for_each(drop_nulls);
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_infer_cell() {
+        assert_eq!(infer_cell("10"), json!(10));
+        assert_eq!(infer_cell("-2.5"), json!(-2.5));
+        assert_eq!(infer_cell("TRUE"), json!(true));
+        assert_eq!(infer_cell(""), Value::Null);
+        assert_eq!(infer_cell("007"), json!("007"));
+        assert_eq!(infer_cell("0.5"), json!(0.5));
+        assert_eq!(infer_cell("NaN"), json!("NaN"));
+        assert_eq!(infer_cell("1.2.3"), json!("1.2.3"));
+        assert_eq!(infer_cell("Italy"), json!("Italy"));
+        assert_eq!(
+            infer_cell("9223372036854775807"),
+            json!(9223372036854775807i64)
+        );
+        assert_eq!(
+            infer_cell("12345678901234567890"),
+            json!("12345678901234567890")
+        );
+        assert_eq!(
+            convert_cell("12345678901234567890", CsvType::Float).unwrap(),
+            json!(12345678901234567890.0)
+        );
+    }
+
+    #[test]
+    fn test_cell_typer_overrides() {
+        let headers = StringRecord::from(vec!["Name", "Kit Number", "Zip"]);
+        let opts = CsvTypeOpts {
+            infer: true,
+            types: vec![("Zip".into(), CsvType::String)],
+        };
+        let typer = CellTyper::new(&headers, &opts).unwrap();
+        let record = StringRecord::from(vec!["Buffon", "77", "10121"]);
+        assert_eq!(
+            typer.row(&headers, &record).unwrap(),
+            json!({"Name": "Buffon", "Kit Number": 77, "Zip": "10121"})
+        );
+
+        let record = StringRecord::from(vec!["Buffon", "77", "10121"]);
+        let opts = CsvTypeOpts {
+            infer: false,
+            types: vec![("Name".into(), CsvType::Integer)],
+        };
+        let typer = CellTyper::new(&headers, &opts).unwrap();
+        assert!(typer.row(&headers, &record).is_err());
+
+        let opts = CsvTypeOpts {
+            infer: false,
+            types: vec![("Missing".into(), CsvType::Integer)],
+        };
+        assert!(CellTyper::new(&headers, &opts).is_err());
+    }
+
+    #[test]
+    fn test_drop_nulls() {
+        let mut value = json!([{"a": 1, "b": null}]);
+        drop_nulls(&mut value);
+        assert_eq!(value, json!([{"a": 1}]));
+    }
+}
@@ -0,0 +1,399 @@
+use std::{collections::HashMap, fs, io::Write};
+
+use anyhow::{anyhow, Result};
+use csv::{StringRecord, Writer};
+
+use super::{
+    csv_convert::CsvRows,
+    csv_infer::CellTyper,
+    csv_ops::{column_indices, csv_writer},
+    csv_unflatten::Unflatten,
+    csv_writer::{record_writer, RecordWriter},
+};
+use crate::cli::{
+    CsvFilterOpts, CsvJoinType, CsvOutputOpts, CsvReaderOpts, CsvTypeOpts, OutputFormat,
+};
+
+// 参与 join 的一侧: 表头, 键列下标以及其余列的下标
+struct JoinSide {
+    headers: StringRecord,
+    keys: Vec<usize>,
+    others: Vec<usize>,
+}
+
+// 不指定 --format 时输出 csv, 否则复用转换命令的各种格式
+enum JoinSink {
+    Csv(Box<Writer<Box<dyn Write + Send>>>),
+    Records(Box<dyn RecordWriter>, CellTyper, Option<Unflatten>),
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn process_csv_join(
+    left: &str,
+    right: &str,
+    output: &str,
+    format: Option<OutputFormat>,
+    how: CsvJoinType,
+    on: &[(String, String)],
+    suffixes: (&str, &str),
+    opts: &CsvReaderOpts,
+    types: &CsvTypeOpts,
+    output_opts: &CsvOutputOpts,
+) -> Result<()> {
+    if on.is_empty() {
+        return Err(anyhow!("At least one --on column is required"));
+    }
+    let left_keys: Vec<String> = on.iter().map(|(l, _)| l.clone()).collect();
+    let right_keys: Vec<String> = on.iter().map(|(_, r)| r.clone()).collect();
+    let (left_rows, left_side) = JoinSide::open(left, opts, &left_keys)?;
+    let (right_rows, right_side) = JoinSide::open(right, opts, &right_keys)?;
+    let headers = join_headers(&left_side, &right_side, suffixes)?;
+
+    let mut sink = match format {
+        Some(format) => {
+            let mut writer = record_writer(format, output, output_opts)?;
+            let unflatten = match output_opts.unflatten {
+                true => Some(Unflatten::new(&headers)?),
+                false => None,
+            };
+            if unflatten.is_none() {
+                let columns: Vec<String> = headers.iter().map(String::from).collect();
+                writer.columns(&columns)?;
+            }
+            JoinSink::Records(writer, CellTyper::new(&headers, types)?, unflatten)
+        }
+        // 输出使用左侧输入的方言
+        None => JoinSink::Csv(Box::new(csv_writer(output, left_rows.dialect(), &headers)?)),
+    };
+
+    // 把较小的一侧放进哈希表, 另一侧流式读取; stdin 只能读一次, 只能作为流式的一侧
+    let build_left = match (input_size(left), input_size(right)) {
+        (None, None) => return Err(anyhow!("Only one side of the join can be stdin")),
+        (None, Some(_)) => false,
+        (Some(_), None) => true,
+        (Some(l), Some(r)) => l <= r,
+    };
+    let ((mut build_rows, build), (mut probe_rows, probe)) = if build_left {
+        ((left_rows, left_side), (right_rows, right_side))
+    } else {
+        ((right_rows, right_side), (left_rows, left_side))
+    };
+    let (keep_build, keep_probe) = match (how, build_left) {
+        (CsvJoinType::Inner, _) => (false, false),
+        (CsvJoinType::Full, _) => (true, true),
+        (CsvJoinType::Left, left) => (left, !left),
+        (CsvJoinType::Right, left) => (!left, left),
+    };
+
+    let mut table: HashMap<Vec<String>, Vec<usize>> = HashMap::new();
+    let mut records = Vec::new();
+    for record in build_rows.by_ref() {
+        let record = record?;
+        table
+            .entry(build.key(&record))
+            .or_default()
+            .push(records.len());
+        records.push(record);
+    }
+    let mut matched = vec![false; records.len()];
+    let emit = |sink: &mut JoinSink,
+                build_record: Option<&StringRecord>,
+                probe_record: Option<&StringRecord>| {
+        let row = pair(build_left, build_record, probe_record);
+        sink.write(&headers, join_row(&build, &probe, build_left, row))
+    };
+
+    // 输出按左侧文件的顺序, 只在右侧出现的行排在最后, 与哪一侧更小无关;
+    // 左侧在哈希表中时, 先暂存匹配到的右侧行, 读完后再按左侧的顺序输出
+    let mut probed = Vec::new();
+    let pending_len = if build_left { records.len() } else { 0 };
+    let mut pending: Vec<Vec<usize>> = vec![Vec::new(); pending_len];
+    let mut unmatched = Vec::new();
+    for record in probe_rows.by_ref() {
+        let record = record?;
+        match table.get(&probe.key(&record)) {
+            Some(indices) if build_left => {
+                for &i in indices {
+                    pending[i].push(probed.len());
+                }
+                probed.push(record);
+            }
+            Some(indices) => {
+                for &i in indices {
+                    matched[i] = true;
+                    emit(&mut sink, Some(&records[i]), Some(&record))?;
+                }
+            }
+            None if keep_probe && build_left => {
+                unmatched.push(probed.len());
+                probed.push(record);
+            }
+            None if keep_probe => emit(&mut sink, None, Some(&record))?,
+            None => {}
+        }
+    }
+
+    if build_left {
+        for (i, record) in records.iter().enumerate() {
+            if pending[i].is_empty() && keep_build {
+                emit(&mut sink, Some(record), None)?;
+            }
+            for &j in &pending[i] {
+                emit(&mut sink, Some(record), Some(&probed[j]))?;
+            }
+        }
+        for &j in &unmatched {
+            emit(&mut sink, None, Some(&probed[j]))?;
+        }
+    } else if keep_build {
+        for (record, _) in records.iter().zip(&matched).filter(|(_, m)| !**m) {
+            emit(&mut sink, Some(record), None)?;
+        }
+    }
+    sink.finish()
+}
+
+impl JoinSide {
+    fn open(input: &str, opts: &CsvReaderOpts, keys: &[String]) -> Result<(CsvRows, Self)> {
+        let rows = CsvRows::open(input, opts, &CsvFilterOpts::default())?;
+        let headers = rows.headers().clone();
+        let keys = column_indices(&headers, keys).map_err(|e| anyhow!("{}: {}", input, e))?;
+        let others = (0..headers.len()).filter(|i| !keys.contains(i)).collect();
+        Ok((
+            rows,
+            Self {
+                headers,
+                keys,
+                others,
+            },
+        ))
+    }
+
+    fn key(&self, record: &StringRecord) -> Vec<String> {
+        self.keys
+            .iter()
+            .map(|&i| record.get(i).unwrap_or("").to_string())
+            .collect()
+    }
+}
+
+impl JoinSink {
+    fn write(&mut self, headers: &StringRecord, record: StringRecord) -> Result<()> {
+        match self {
+            JoinSink::Csv(writer) => Ok(writer.write_record(&record)?),
+            JoinSink::Records(writer, typer, unflatten) => {
+                let value = typer.row(headers, &record)?;
+                match unflatten {
+                    Some(unflatten) => writer.write(unflatten.apply(value)),
+                    None => writer.write(value),
+                }
+            }
+        }
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        match self {
+            JoinSink::Csv(writer) => Ok(writer.flush()?),
+            JoinSink::Records(writer, ..) => writer.finish(),
+        }
+    }
+}
+
+// 键列只输出一次 (使用左侧的列名), 两侧重名的其他列加上后缀区分
+fn join_headers(left: &JoinSide, right: &JoinSide, suffixes: (&str, &str)) -> Result<StringRecord> {
+    let (lh, rh) = (&left.headers, &right.headers);
+    let keys: Vec<&str> = left.keys.iter().map(|&i| &lh[i]).collect();
+    let left_names: Vec<&str> = left.others.iter().map(|&i| &lh[i]).collect();
+    let right_names: Vec<&str> = right.others.iter().map(|&i| &rh[i]).collect();
+
+    // 加上后缀后与已有的列重名时继续加后缀, 比如左侧同时有 `id` 和 `id_left`
+    let all: Vec<&str> = keys
+        .iter()
+        .chain(&left_names)
+        .chain(&right_names)
+        .copied()
+        .collect();
+    let mut headers = StringRecord::new();
+    let suffixed = |headers: &StringRecord, name: &str, suffix: &str| {
+        let mut name = format!("{}{}", name, suffix);
+        while all.contains(&name.as_str()) || headers.iter().any(|h| h == name) {
+            if suffix.is_empty() {
+                return Err(anyhow!("Duplicate column in join output: {}", name));
+            }
+            name.push_str(suffix);
+        }
+        Ok(name)
+    };
+    for name in &keys {
+        headers.push_field(name);
+    }
+    for name in &left_names {
+        if right_names.contains(name) {
+            let name = suffixed(&headers, name, suffixes.0)?;
+            headers.push_field(&name);
+        } else {
+            headers.push_field(name);
+        }
+    }
+    for name in &right_names {
+        if left_names.contains(name) || keys.contains(name) {
+            let name = suffixed(&headers, name, suffixes.1)?;
+            headers.push_field(&name);
+        } else {
+            headers.push_field(name);
+        }
+    }
+    Ok(headers)
+}
+
+// 把 (build, probe) 还原成 (left, right)
+fn pair<'a>(
+    build_left: bool,
+    build: Option<&'a StringRecord>,
+    probe: Option<&'a StringRecord>,
+) -> (Option<&'a StringRecord>, Option<&'a StringRecord>) {
+    if build_left {
+        (build, probe)
+    } else {
+        (probe, build)
+    }
+}
+
+fn join_row(
+    build: &JoinSide,
+    probe: &JoinSide,
+    build_left: bool,
+    (left, right): (Option<&StringRecord>, Option<&StringRecord>),
+) -> StringRecord {
+    let (left_side, right_side) = if build_left {
+        (build, probe)
+    } else {
+        (probe, build)
+    };
+    let mut row = StringRecord::new();
+    // 外连接时键取自存在的那一侧
+    let (key_side, key_record) = match (left, right) {
+        (Some(l), _) => (left_side, l),
+        (None, Some(r)) => (right_side, r),
+        (None, None) => unreachable!("join row without any side"),
+    };
+    for &i in &key_side.keys {
+        row.push_field(key_record.get(i).unwrap_or(""));
+    }
+    for (side, record) in [(left_side, left), (right_side, right)] {
+        for &i in &side.others {
+            row.push_field(record.and_then(|r| r.get(i)).unwrap_or(""));
+        }
+    }
+    row
+}
+
+fn input_size(input: &str) -> Option<u64> {
+    if input == "-" {
+        return None;
+    }
+    fs::metadata(input).ok().map(|m| m.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{super::test_utils::temp_file, *};
+    use tempfile::NamedTempFile;
+
+    fn join(left: &str, right: &str, how: CsvJoinType) -> String {
+        let (left, right) = (temp_file(left), temp_file(right));
+        let output = NamedTempFile::new().unwrap();
+        process_csv_join(
+            left.path().to_str().unwrap(),
+            right.path().to_str().unwrap(),
+            output.path().to_str().unwrap(),
+            None,
+            how,
+            &[("Name".to_string(), "Player".to_string())],
+            ("_left", "_right"),
+            &CsvReaderOpts::default(),
+            &CsvTypeOpts::default(),
+            &CsvOutputOpts::default(),
+        )
+        .unwrap();
+        fs::read_to_string(output.path()).unwrap()
+    }
+
+    const ROSTER: &str = "Name,Position,Kit Number\n\
+        Paulo Dybala,Second Striker,10\n\
+        Mattia Perin,Goalkeeper,37\n\
+        Sami Khedira,Central Midfield,6\n";
+
+    // 比 ROSTER 短, 因此会被放进哈希表
+    const GOALS: &str = "Player,Goals,Kit Number\n\
+        Sami Khedira,2,6\n\
+        Paulo Dybala,11,10\n\
+        Emre Can,1,23\n";
+
+    #[test]
+    fn test_join_inner_and_left() {
+        assert_eq!(
+            join(ROSTER, GOALS, CsvJoinType::Inner),
+            "Name,Position,Kit Number_left,Goals,Kit Number_right\n\
+             Paulo Dybala,Second Striker,10,11,10\n\
+             Sami Khedira,Central Midfield,6,2,6\n"
+        );
+        assert_eq!(
+            join(ROSTER, GOALS, CsvJoinType::Left),
+            "Name,Position,Kit Number_left,Goals,Kit Number_right\n\
+             Paulo Dybala,Second Striker,10,11,10\n\
+             Mattia Perin,Goalkeeper,37,,\n\
+             Sami Khedira,Central Midfield,6,2,6\n"
+        );
+    }
+
+    #[test]
+    fn test_join_right_and_full() {
+        assert_eq!(
+            join(ROSTER, GOALS, CsvJoinType::Right),
+            "Name,Position,Kit Number_left,Goals,Kit Number_right\n\
+             Paulo Dybala,Second Striker,10,11,10\n\
+             Sami Khedira,Central Midfield,6,2,6\n\
+             Emre Can,,,1,23\n"
+        );
+        let full = join(ROSTER, GOALS, CsvJoinType::Full);
+        assert_eq!(full.lines().count(), 5);
+        assert!(full.contains("Mattia Perin,Goalkeeper,37,,\n"));
+        assert!(full.contains("Emre Can,,,1,23\n"));
+    }
+
+    #[test]
+    fn test_join_suffix_collision() {
+        let left = "Name,Kit Number,Kit Number_left\nPaulo Dybala,10,21\n";
+        assert_eq!(
+            join(left, GOALS, CsvJoinType::Inner),
+            "Name,Kit Number_left_left,Kit Number_left,Goals,Kit Number_right\n\
+             Paulo Dybala,10,21,11,10\n"
+        );
+    }
+
+    #[test]
+    fn test_join_keeps_left_order_when_left_is_smaller() {
+        // 比 GOALS 短, 左侧会被放进哈希表
+        let left = "Name,Kit Number\n\
+            Sami Khedira,6\n\
+            Mattia Perin,37\n\
+            Paulo Dybala,10\n";
+        assert!(left.len() < GOALS.len());
+        assert_eq!(
+            join(left, GOALS, CsvJoinType::Left),
+            "Name,Kit Number_left,Goals,Kit Number_right\n\
+             Sami Khedira,6,2,6\n\
+             Mattia Perin,37,,\n\
+             Paulo Dybala,10,11,10\n"
+        );
+        assert_eq!(
+            join(left, GOALS, CsvJoinType::Full),
+            "Name,Kit Number_left,Goals,Kit Number_right\n\
+             Sami Khedira,6,2,6\n\
+             Mattia Perin,37,,\n\
+             Paulo Dybala,10,11,10\n\
+             Emre Can,,1,23\n"
+        );
+    }
+}
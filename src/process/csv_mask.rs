@@ -0,0 +1,196 @@
+use anyhow::{anyhow, Result};
+use base64::{prelude::BASE64_URL_SAFE_NO_PAD, Engine};
+use csv::StringRecord;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use super::{
+    csv_convert::CsvRows,
+    csv_ops::{column_indices, csv_writer},
+    text::{Blake3, KeyLoader},
+};
+use crate::cli::{CsvFilterOpts, CsvReaderOpts, MaskStrategy};
+
+const REDACTED: &str = "[REDACTED]";
+
+// partial 保留末尾的字母和数字个数
+const KEEP_LAST: usize = 4;
+
+struct Masker {
+    rules: Vec<(usize, MaskStrategy)>,
+    key: Option<Blake3>,
+}
+
+pub fn process_csv_mask(
+    input: &str,
+    output: &str,
+    columns: &[(String, MaskStrategy)],
+    key: Option<&str>,
+    opts: &CsvReaderOpts,
+) -> Result<()> {
+    let key = key.map(Blake3::load).transpose()?;
+    if key.is_none() && columns.iter().any(|(_, s)| *s == MaskStrategy::Hash) {
+        return Err(anyhow!("--key is required for hash masking"));
+    }
+    let mut rows = CsvRows::open(input, opts, &CsvFilterOpts::default())?;
+    let names: Vec<String> = columns.iter().map(|(name, _)| name.clone()).collect();
+    let indices = column_indices(rows.headers(), &names)?;
+    let masker = Masker {
+        rules: indices
+            .into_iter()
+            .zip(columns.iter().map(|(_, s)| *s))
+            .collect(),
+        key,
+    };
+
+    let mut writer = csv_writer(output, rows.dialect(), rows.headers())?;
+    for record in rows.by_ref() {
+        writer.write_record(&masker.apply(&record?))?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+impl Masker {
+    fn apply(&self, record: &StringRecord) -> StringRecord {
+        let mut fields: Vec<String> = record.iter().map(String::from).collect();
+        for (idx, strategy) in &self.rules {
+            if let Some(cell) = fields.get_mut(*idx) {
+                *cell = self.mask(cell, *strategy);
+            }
+        }
+        StringRecord::from(fields)
+    }
+
+    // 空单元格保持为空, 不泄露也不伪造信息
+    fn mask(&self, cell: &str, strategy: MaskStrategy) -> String {
+        if cell.is_empty() {
+            return String::new();
+        }
+        match strategy {
+            MaskStrategy::Hash => {
+                let key = self.key.as_ref().expect("checked in process_csv_mask");
+                BASE64_URL_SAFE_NO_PAD.encode(key.keyed_hash(cell.as_bytes()).as_bytes())
+            }
+            MaskStrategy::Redact => REDACTED.to_string(),
+            MaskStrategy::Partial => partial(cell),
+            MaskStrategy::Fake => {
+                // 有密钥时同样的输入得到同样的假数据, 与 hash 使用不同的输入避免互相推导
+                let mut rng = match &self.key {
+                    Some(key) => StdRng::from_seed(*key.keyed_hash(&fake_seed(cell)).as_bytes()),
+                    None => StdRng::from_entropy(),
+                };
+                fake(cell, &mut rng)
+            }
+        }
+    }
+}
+
+// 4111-1111-1111-1234 => ****-****-****-1234, 太短的值全部遮住
+fn partial(cell: &str) -> String {
+    let total = cell.chars().filter(|c| c.is_alphanumeric()).count();
+    let keep = if total > KEEP_LAST { KEEP_LAST } else { 0 };
+    let mut seen = 0;
+    cell.chars()
+        .map(|c| {
+            if !c.is_alphanumeric() {
+                return c;
+            }
+            seen += 1;
+            if seen > total - keep {
+                c
+            } else {
+                '*'
+            }
+        })
+        .collect()
+}
+
+// 保留格式: 数字换成数字, 字母换成同样大小写的字母, 其余字符不变
+fn fake(cell: &str, rng: &mut StdRng) -> String {
+    cell.chars()
+        .map(|c| {
+            if c.is_ascii_digit() {
+                rng.gen_range(b'0'..=b'9') as char
+            } else if c.is_uppercase() {
+                rng.gen_range(b'A'..=b'Z') as char
+            } else if c.is_alphabetic() {
+                rng.gen_range(b'a'..=b'z') as char
+            } else {
+                c
+            }
+        })
+        .collect()
+}
+
+fn fake_seed(cell: &str) -> Vec<u8> {
+    let mut seed = b"rcli-csv-mask-fake:".to_vec();
+    seed.extend_from_slice(cell.as_bytes());
+    seed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn masker(rules: Vec<(usize, MaskStrategy)>) -> Masker {
+        Masker {
+            rules,
+            key: Some(Blake3::load("fixture/blake3.txt").unwrap()),
+        }
+    }
+
+    #[test]
+    fn test_mask_strategies() {
+        let masker = masker(vec![
+            (0, MaskStrategy::Hash),
+            (1, MaskStrategy::Redact),
+            (2, MaskStrategy::Partial),
+        ]);
+        let record = StringRecord::from(vec!["Gianluigi Buffon", "Jan 28, 1978 (41)", "IT60-X054"]);
+        let masked = masker.apply(&record);
+        assert_eq!(masked.len(), 3);
+        assert_eq!(masked[0].len(), 43);
+        assert_ne!(&masked[0], "Gianluigi Buffon");
+        assert_eq!(&masked[1], REDACTED);
+        assert_eq!(&masked[2], "****-X054");
+
+        // 同一个值在不同文件中得到同样的假名, join 仍然可用
+        let again = masker.apply(&StringRecord::from(vec!["Gianluigi Buffon", "", "1234"]));
+        assert_eq!(&again[0], &masked[0]);
+        assert_eq!(&again[1], "");
+        assert_eq!(&again[2], "****");
+    }
+
+    #[test]
+    fn test_mask_fake() {
+        let masker = masker(vec![(0, MaskStrategy::Fake)]);
+        let dob = "Apr 18, 1990 (29)";
+        let fake = masker.mask(dob, MaskStrategy::Fake);
+        assert_ne!(fake, dob);
+        assert_eq!(fake, masker.mask(dob, MaskStrategy::Fake));
+        let shape = |s: &str| -> String {
+            s.chars()
+                .map(|c| match c {
+                    '0'..='9' => '9',
+                    'A'..='Z' => 'A',
+                    'a'..='z' => 'a',
+                    c => c,
+                })
+                .collect()
+        };
+        assert_eq!(shape(&fake), shape(dob));
+    }
+
+    #[test]
+    fn test_mask_requires_key() {
+        let err = process_csv_mask(
+            "assets/juventus.csv",
+            "-",
+            &[("Name".to_string(), MaskStrategy::Hash)],
+            None,
+            &CsvReaderOpts::default(),
+        )
+        .unwrap_err();
+        assert_eq!(err.to_string(), "--key is required for hash masking");
+    }
+}
@@ -0,0 +1,383 @@
+use std::{
+    cmp::Ordering,
+    collections::{BinaryHeap, HashSet},
+    fs::File,
+    io::{BufReader, BufWriter, Seek, SeekFrom, Write},
+};
+
+use anyhow::{anyhow, Result};
+use csv::{ReaderBuilder, StringRecord, Writer, WriterBuilder};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use super::csv_convert::CsvRows;
+use crate::{
+    cli::{CsvFilterOpts, CsvReaderOpts, CsvSortKey},
+    get_writer,
+};
+
+// 排序时每条记录除了字段内容之外的大致开销 (StringRecord 的索引等)
+const RECORD_OVERHEAD: usize = 64;
+
+// 已解析到列下标的排序键
+struct SortSpec {
+    keys: Vec<(usize, bool, bool)>,
+}
+
+// 外部归并时堆里的元素: 当前记录以及它来自哪个分块
+struct MergeItem<'a> {
+    record: StringRecord,
+    chunk: usize,
+    spec: &'a SortSpec,
+}
+
+pub fn process_csv_sort(
+    input: &str,
+    output: &str,
+    opts: &CsvReaderOpts,
+    filter: &CsvFilterOpts,
+    keys: &[CsvSortKey],
+    memory_mb: usize,
+) -> Result<()> {
+    let mut rows = CsvRows::open(input, opts, filter)?;
+    let spec = SortSpec::new(rows.headers(), keys)?;
+    let budget = memory_mb.max(1) * 1024 * 1024;
+
+    let mut buffer = Vec::new();
+    let mut used = 0;
+    let mut chunks = Vec::new();
+    for record in rows.by_ref() {
+        let record = record?;
+        used += record.as_slice().len() + RECORD_OVERHEAD;
+        buffer.push(record);
+        // 超出内存预算时把排好序的分块写到临时文件
+        if used >= budget {
+            chunks.push(spill_chunk(&mut buffer, &spec)?);
+            used = 0;
+        }
+    }
+
+    let mut writer = csv_writer(output, rows.dialect(), rows.headers())?;
+    if chunks.is_empty() {
+        buffer.sort_by(|a, b| spec.compare(a, b));
+        for record in &buffer {
+            writer.write_record(record)?;
+        }
+    } else {
+        if !buffer.is_empty() {
+            chunks.push(spill_chunk(&mut buffer, &spec)?);
+        }
+        merge_chunks(chunks, &spec, &mut writer)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+pub fn process_csv_dedupe(
+    input: &str,
+    output: &str,
+    opts: &CsvReaderOpts,
+    filter: &CsvFilterOpts,
+    key: &[String],
+) -> Result<()> {
+    let mut rows = CsvRows::open(input, opts, filter)?;
+    let indices = column_indices(rows.headers(), key)?;
+    let mut writer = csv_writer(output, rows.dialect(), rows.headers())?;
+
+    // 只保存键的 blake3 哈希, 内存占用与记录长度无关
+    let mut seen = HashSet::new();
+    for record in rows.by_ref() {
+        let record = record?;
+        let mut hasher = blake3::Hasher::new();
+        let fields: Box<dyn Iterator<Item = &str>> = if indices.is_empty() {
+            Box::new(record.iter())
+        } else {
+            Box::new(indices.iter().map(|&i| record.get(i).unwrap_or("")))
+        };
+        for field in fields {
+            hasher.update(&(field.len() as u64).to_le_bytes());
+            hasher.update(field.as_bytes());
+        }
+        if seen.insert(*hasher.finalize().as_bytes()) {
+            writer.write_record(&record)?;
+        }
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+pub fn process_csv_sample(
+    input: &str,
+    output: &str,
+    opts: &CsvReaderOpts,
+    filter: &CsvFilterOpts,
+    size: usize,
+    seed: Option<u64>,
+) -> Result<()> {
+    let mut rows = CsvRows::open(input, opts, filter)?;
+    let mut rng = match seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_entropy(),
+    };
+
+    // 蓄水池抽样, 只需要遍历一次且内存只保存 size 条记录
+    let mut reservoir: Vec<(usize, StringRecord)> = Vec::with_capacity(size);
+    for (i, record) in rows.by_ref().enumerate() {
+        let record = record?;
+        if reservoir.len() < size {
+            reservoir.push((i, record));
+        } else {
+            let j = rng.gen_range(0..=i);
+            if j < size {
+                reservoir[j] = (i, record);
+            }
+        }
+    }
+    // 按原始顺序输出
+    reservoir.sort_by_key(|(i, _)| *i);
+
+    let mut writer = csv_writer(output, rows.dialect(), rows.headers())?;
+    for (_, record) in &reservoir {
+        writer.write_record(record)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+// 输出保持与输入相同的分隔符和引号; 输入没有表头时也不写自动生成的 col1..colN
+// opts 应该是 CsvRows::dialect(), 其中包含 --sniff 检测到的方言
+pub(crate) fn csv_writer(
+    output: &str,
+    opts: &CsvReaderOpts,
+    headers: &StringRecord,
+) -> Result<Writer<Box<dyn Write + Send>>> {
+    let mut writer = csv_writer_builder(opts).from_writer(get_writer(output)?);
+    if opts.header {
+        writer.write_record(headers)?;
+    }
+    Ok(writer)
+}
+
+pub(crate) fn csv_writer_builder(opts: &CsvReaderOpts) -> WriterBuilder {
+    let mut builder = WriterBuilder::new();
+    builder
+        .delimiter(opts.delimiter)
+        .quote(opts.quote)
+        .flexible(opts.flexible);
+    builder
+}
+
+pub(crate) fn column_indices(headers: &StringRecord, columns: &[String]) -> Result<Vec<usize>> {
+    columns
+        .iter()
+        .map(|name| {
+            headers
+                .iter()
+                .position(|h| h == name)
+                .ok_or_else(|| anyhow!("Unknown column: {}", name))
+        })
+        .collect()
+}
+
+impl SortSpec {
+    fn new(headers: &StringRecord, keys: &[CsvSortKey]) -> Result<Self> {
+        if keys.is_empty() {
+            return Err(anyhow!("At least one --by column is required"));
+        }
+        let names: Vec<String> = keys.iter().map(|k| k.column.clone()).collect();
+        let indices = column_indices(headers, &names)?;
+        Ok(Self {
+            keys: indices
+                .into_iter()
+                .zip(keys)
+                .map(|(i, k)| (i, k.desc, k.numeric))
+                .collect(),
+        })
+    }
+
+    fn compare(&self, a: &StringRecord, b: &StringRecord) -> Ordering {
+        for &(idx, desc, numeric) in &self.keys {
+            let (x, y) = (a.get(idx).unwrap_or(""), b.get(idx).unwrap_or(""));
+            let ord = if numeric {
+                // 无法解析成数字的值排在最后
+                match (x.trim().parse::<f64>(), y.trim().parse::<f64>()) {
+                    (Ok(x), Ok(y)) => x.total_cmp(&y),
+                    (Ok(_), Err(_)) => Ordering::Less,
+                    (Err(_), Ok(_)) => Ordering::Greater,
+                    (Err(_), Err(_)) => x.cmp(y),
+                }
+            } else {
+                x.cmp(y)
+            };
+            let ord = if desc { ord.reverse() } else { ord };
+            if ord != Ordering::Equal {
+                return ord;
+            }
+        }
+        Ordering::Equal
+    }
+}
+
+fn spill_chunk(buffer: &mut Vec<StringRecord>, spec: &SortSpec) -> Result<File> {
+    buffer.sort_by(|a, b| spec.compare(a, b));
+    let file = tempfile::tempfile()?;
+    let mut writer = WriterBuilder::new()
+        .flexible(true)
+        .from_writer(BufWriter::new(file));
+    for record in buffer.drain(..) {
+        writer.write_record(&record)?;
+    }
+    let mut file = writer
+        .into_inner()
+        .map_err(|e| anyhow!("Failed to flush sort chunk: {}", e))?
+        .into_inner()?;
+    file.seek(SeekFrom::Start(0))?;
+    Ok(file)
+}
+
+fn merge_chunks(
+    chunks: Vec<File>,
+    spec: &SortSpec,
+    writer: &mut Writer<Box<dyn Write + Send>>,
+) -> Result<()> {
+    let mut readers: Vec<_> = chunks
+        .into_iter()
+        .map(|file| {
+            ReaderBuilder::new()
+                .has_headers(false)
+                .flexible(true)
+                .from_reader(BufReader::new(file))
+        })
+        .collect();
+    let mut heap = BinaryHeap::new();
+    for (chunk, reader) in readers.iter_mut().enumerate() {
+        let mut record = StringRecord::new();
+        if reader.read_record(&mut record)? {
+            heap.push(MergeItem {
+                record,
+                chunk,
+                spec,
+            });
+        }
+    }
+    while let Some(MergeItem { record, chunk, .. }) = heap.pop() {
+        writer.write_record(&record)?;
+        let mut next = StringRecord::new();
+        if readers[chunk].read_record(&mut next)? {
+            heap.push(MergeItem {
+                record: next,
+                chunk,
+                spec,
+            });
+        }
+    }
+    Ok(())
+}
+
+// BinaryHeap 是大顶堆, 这里反过来比较; 相等时先输出靠前的分块, 保证排序稳定
+impl Ord for MergeItem<'_> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.spec
+            .compare(&self.record, &other.record)
+            .then(self.chunk.cmp(&other.chunk))
+            .reverse()
+    }
+}
+
+impl PartialOrd for MergeItem<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for MergeItem<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for MergeItem<'_> {}
+
+#[cfg(test)]
+mod tests {
+    use super::{super::test_utils::temp_file, *};
+
+    fn spec(keys: Vec<(usize, bool, bool)>) -> SortSpec {
+        SortSpec { keys }
+    }
+
+    #[test]
+    fn test_sort_spec_compare() {
+        let a = StringRecord::from(vec!["Italy", "9"]);
+        let b = StringRecord::from(vec!["Italy", "10"]);
+        let c = StringRecord::from(vec!["Brazil", ""]);
+        // 字符串比较时 "10" < "9"
+        assert_eq!(
+            spec(vec![(1, false, false)]).compare(&a, &b),
+            Ordering::Greater
+        );
+        assert_eq!(spec(vec![(1, false, true)]).compare(&a, &b), Ordering::Less);
+        assert_eq!(
+            spec(vec![(1, true, true)]).compare(&a, &b),
+            Ordering::Greater
+        );
+        assert_eq!(
+            spec(vec![(1, false, true)]).compare(&c, &a),
+            Ordering::Greater
+        );
+        assert_eq!(
+            spec(vec![(0, false, false), (1, true, true)]).compare(&c, &b),
+            Ordering::Less
+        );
+    }
+
+    #[test]
+    fn test_external_merge_is_sorted_and_stable() {
+        let spec = spec(vec![(0, false, true)]);
+        let mut chunks = Vec::new();
+        for chunk in [vec!["3", "1", "2"], vec!["2", "5", "0"]] {
+            let mut buffer: Vec<StringRecord> = chunk
+                .into_iter()
+                .enumerate()
+                .map(|(i, v)| StringRecord::from(vec![v.to_string(), format!("c{}", i)]))
+                .collect();
+            chunks.push(spill_chunk(&mut buffer, &spec).unwrap());
+        }
+        let path = tempfile::NamedTempFile::new().unwrap();
+        let output = path.path().to_str().unwrap();
+        {
+            let mut writer = WriterBuilder::new().from_writer(get_writer(output).unwrap());
+            merge_chunks(chunks, &spec, &mut writer).unwrap();
+            writer.flush().unwrap();
+        }
+        let content = std::fs::read_to_string(output).unwrap();
+        assert_eq!(content, "0,c2\n1,c1\n2,c2\n2,c0\n3,c0\n5,c1\n");
+    }
+
+    #[test]
+    fn test_sort_keeps_sniffed_dialect() {
+        let input = temp_file("b;2\na;1\nc;3\n");
+        let out = tempfile::NamedTempFile::new().unwrap();
+        let opts = CsvReaderOpts {
+            sniff: true,
+            ..Default::default()
+        };
+        let keys = [CsvSortKey {
+            column: "col1".to_string(),
+            desc: false,
+            numeric: false,
+        }];
+        process_csv_sort(
+            input.path().to_str().unwrap(),
+            out.path().to_str().unwrap(),
+            &opts,
+            &CsvFilterOpts::default(),
+            &keys,
+            64,
+        )
+        .unwrap();
+        assert_eq!(
+            std::fs::read_to_string(out.path()).unwrap(),
+            "a;1\nb;2\nc;3\n"
+        );
+    }
+}
@@ -0,0 +1,176 @@
+use std::io::Write;
+
+use anyhow::{anyhow, Result};
+use csv::StringRecord;
+use rusqlite::{types::ValueRef, Connection};
+use serde_json::{Map, Number, Value};
+
+use super::{
+    csv_convert::CsvRows,
+    csv_infer::CellTyper,
+    csv_sql::SqliteTable,
+    csv_table::{render_table, terminal_width},
+    csv_writer::record_writer,
+};
+use crate::{
+    cli::{CsvFilterOpts, CsvOutputOpts, CsvReaderOpts, CsvTypeOpts, OutputFormat},
+    get_writer,
+};
+
+// 导入时每个事务插入的行数
+const LOAD_BATCH: usize = 10_000;
+
+pub fn process_csv_query(
+    sql: &str,
+    tables: &[(String, String)],
+    output: &str,
+    format: Option<OutputFormat>,
+    opts: &CsvReaderOpts,
+) -> Result<()> {
+    if tables.is_empty() {
+        return Err(anyhow!("At least one --table is required"));
+    }
+    let mut conn = Connection::open_in_memory()?;
+    for (name, input) in tables {
+        load_csv(&mut conn, name, input, opts)?;
+    }
+
+    let mut stmt = conn.prepare(sql)?;
+    let headers = column_names(&stmt);
+    let mut rows = stmt.query([])?;
+    match format {
+        Some(format) => {
+            // --table 在 query 中表示输入的表, 因此不支持 CsvOutputOpts 的配置
+            let mut writer = record_writer(format, output, &CsvOutputOpts::default())?;
+            let columns: Vec<String> = headers.iter().map(String::from).collect();
+            writer.columns(&columns)?;
+            while let Some(row) = rows.next()? {
+                let mut map = Map::with_capacity(headers.len());
+                for (i, name) in headers.iter().enumerate() {
+                    map.insert(name.to_string(), json_value(row.get_ref(i)?));
+                }
+                writer.write(Value::Object(map))?;
+            }
+            writer.finish()
+        }
+        None => {
+            let mut records = Vec::new();
+            while let Some(row) = rows.next()? {
+                let record: StringRecord = (0..headers.len())
+                    .map(|i| Ok(cell_text(row.get_ref(i)?)))
+                    .collect::<Result<_>>()?;
+                records.push(Some(record));
+            }
+            let width = if output == "-" {
+                terminal_width()
+            } else {
+                None
+            };
+            let mut writer = get_writer(output)?;
+            write!(writer, "{}", render_table(&headers, &records, width, false))?;
+            writeln!(writer, "({} rows)", records.len())?;
+            Ok(())
+        }
+    }
+}
+
+// 为了让比较和聚合按数字进行, 导入时总是推断类型
+fn load_csv(conn: &mut Connection, name: &str, input: &str, opts: &CsvReaderOpts) -> Result<()> {
+    let mut rows = CsvRows::open(input, opts, &CsvFilterOpts::default())?;
+    let typer = CellTyper::new(
+        rows.headers(),
+        &rows.type_opts(&CsvTypeOpts {
+            infer: true,
+            ..Default::default()
+        }),
+    )?;
+    let columns = rows.headers().iter().map(String::from).collect();
+    let mut table = SqliteTable::new(name, LOAD_BATCH, Some(columns))?;
+    while let Some(record) = rows.next() {
+        let record = record?;
+        let value = typer
+            .row(rows.headers(), &record)
+            .map_err(|e| anyhow!("{}: line {}, {}", input, rows.line(), e))?;
+        table.push(value)?;
+    }
+    table.load(conn)
+}
+
+// 结果中重名的列 (比如 SELECT a.id, b.id) 加上序号区分
+fn column_names(stmt: &rusqlite::Statement) -> StringRecord {
+    let mut headers = StringRecord::new();
+    for name in stmt.column_names() {
+        let mut unique = name.to_string();
+        let mut n = 1;
+        while headers.iter().any(|h| h == unique) {
+            n += 1;
+            unique = format!("{}_{}", name, n);
+        }
+        headers.push_field(&unique);
+    }
+    headers
+}
+
+fn json_value(value: ValueRef) -> Value {
+    match value {
+        ValueRef::Null => Value::Null,
+        ValueRef::Integer(i) => Value::Number(i.into()),
+        ValueRef::Real(f) => Number::from_f64(f).map_or(Value::Null, Value::Number),
+        ValueRef::Text(t) | ValueRef::Blob(t) => {
+            Value::String(String::from_utf8_lossy(t).into_owned())
+        }
+    }
+}
+
+fn cell_text(value: ValueRef) -> String {
+    match json_value(value) {
+        Value::Null => String::new(),
+        Value::String(s) => s,
+        v => v.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_query_over_csv() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        load_csv(
+            &mut conn,
+            "players",
+            "assets/juventus.csv",
+            &CsvReaderOpts::default(),
+        )
+        .unwrap();
+        let mut stmt = conn
+            .prepare(
+                "SELECT Position, count(*) AS n, max(\"Kit Number\") AS kit FROM players \
+                 GROUP BY Position ORDER BY n DESC, Position LIMIT 2",
+            )
+            .unwrap();
+        assert_eq!(column_names(&stmt), vec!["Position", "n", "kit"]);
+        let rows: Vec<(String, i64, i64)> = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(
+            rows,
+            vec![
+                ("Central Midfield".to_string(), 6, 30),
+                ("Centre-Back".to_string(), 5, 28)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_query_duplicate_columns() {
+        let conn = Connection::open_in_memory().unwrap();
+        let stmt = conn.prepare("SELECT 1 AS id, 2 AS id, 3 AS id").unwrap();
+        assert_eq!(column_names(&stmt), vec!["id", "id_2", "id_3"]);
+        assert_eq!(cell_text(ValueRef::Real(1.5)), "1.5");
+        assert_eq!(cell_text(ValueRef::Null), "");
+    }
+}
@@ -0,0 +1,224 @@
+use std::{fmt, io::Write};
+
+use anyhow::Result;
+use csv::{ByteRecord, ErrorKind, Position, StringRecord, Writer, WriterBuilder};
+
+use crate::{
+    cli::{CsvErrorOpts, OnError},
+    get_writer,
+};
+
+// 无法解析或无法转换类型的一行, 作为 anyhow 的错误传递, 需要时 downcast 出来处理
+#[derive(Debug)]
+pub(crate) struct BadRow {
+    pub(crate) line: u64,
+    pub(crate) byte: u64,
+    pub(crate) reason: String,
+    // 原始字段, 不是合法 UTF-8 的部分用 U+FFFD 代替
+    pub(crate) fields: Vec<String>,
+}
+
+// 按 --on-error 处理坏行, 并统计读入、写出和拒绝的行数
+pub(crate) struct RowErrors {
+    mode: OnError,
+    quarantine: Option<(String, Writer<Box<dyn Write + Send>>)>,
+    rejected: u64,
+}
+
+impl BadRow {
+    pub(crate) fn new(pos: Option<&Position>, reason: String, record: &StringRecord) -> Self {
+        Self {
+            line: pos.map_or(0, |p| p.line()),
+            byte: pos.map_or(0, |p| p.byte()),
+            reason,
+            fields: record.iter().map(String::from).collect(),
+        }
+    }
+
+    // 读取失败时 record 中是已经读到的字段; io 错误不属于某一行, 原样返回
+    pub(crate) fn from_csv(err: csv::Error, record: &ByteRecord) -> anyhow::Error {
+        let reason = match err.kind() {
+            ErrorKind::Io(_) => return err.into(),
+            ErrorKind::UnequalLengths {
+                expected_len, len, ..
+            } => format!("expected {} fields, found {}", expected_len, len),
+            _ => err.to_string(),
+        };
+        let pos = err.position().or(record.position());
+        Self::from_bytes(pos, reason, record).into()
+    }
+
+    pub(crate) fn from_utf8(err: csv::FromUtf8Error) -> anyhow::Error {
+        let reason = format!("invalid UTF-8 in field {}", err.utf8_error().field() + 1);
+        let record = err.into_byte_record();
+        Self::from_bytes(record.position(), reason, &record).into()
+    }
+
+    fn from_bytes(pos: Option<&Position>, reason: String, record: &ByteRecord) -> Self {
+        Self {
+            line: pos.map_or(0, |p| p.line()),
+            byte: pos.map_or(0, |p| p.byte()),
+            reason,
+            fields: record
+                .iter()
+                .map(|f| String::from_utf8_lossy(f).into_owned())
+                .collect(),
+        }
+    }
+}
+
+impl fmt::Display for BadRow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}, byte {}: {}", self.line, self.byte, self.reason)
+    }
+}
+
+impl std::error::Error for BadRow {}
+
+impl RowErrors {
+    pub(crate) fn new(opts: &CsvErrorOpts, headers: &StringRecord) -> Result<Self> {
+        let quarantine = match (opts.on_error, &opts.quarantine) {
+            (OnError::Quarantine, Some(path)) => {
+                // 坏行的字段数不一定与表头相同
+                let mut writer = WriterBuilder::new()
+                    .flexible(true)
+                    .from_writer(get_writer(path)?);
+                writer.write_record(["line", "byte", "reason"].into_iter().chain(headers))?;
+                Some((path.clone(), writer))
+            }
+            (OnError::Quarantine, None) => {
+                anyhow::bail!("--quarantine is required with --on-error quarantine")
+            }
+            _ => None,
+        };
+        Ok(Self {
+            mode: opts.on_error,
+            quarantine,
+            rejected: 0,
+        })
+    }
+
+    // 坏行按配置跳过或者写到隔离文件, 其他错误总是返回
+    pub(crate) fn handle(&mut self, err: anyhow::Error) -> Result<()> {
+        let bad = match err.downcast::<BadRow>() {
+            Ok(bad) if self.mode != OnError::Fail => bad,
+            Ok(bad) => return Err(bad.into()),
+            Err(err) => return Err(err),
+        };
+        self.rejected += 1;
+        if let Some((_, writer)) = &mut self.quarantine {
+            let (line, byte) = (bad.line.to_string(), bad.byte.to_string());
+            writer.write_record(
+                [line.as_str(), byte.as_str(), bad.reason.as_str()]
+                    .into_iter()
+                    .chain(bad.fields.iter().map(String::as_str)),
+            )?;
+        }
+        Ok(())
+    }
+
+    // 输出到 stderr, 不影响写到 stdout 的结果
+    // read = written + filtered + rejected
+    pub(crate) fn finish(
+        self,
+        read: u64,
+        filtered: u64,
+        written: u64,
+        verbose: bool,
+    ) -> Result<()> {
+        if let Some((path, mut writer)) = self.quarantine {
+            writer.flush()?;
+            if self.rejected > 0 {
+                eprintln!("Rejected rows written to {}", path);
+            }
+        }
+        if verbose || self.mode != OnError::Fail {
+            eprintln!(
+                "Read {} rows, wrote {}, filtered {}, rejected {}",
+                read, written, filtered, self.rejected
+            );
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use csv::ReaderBuilder;
+    use tempfile::NamedTempFile;
+
+    use super::*;
+
+    fn bad_rows(data: &[u8]) -> Vec<anyhow::Error> {
+        let mut reader = ReaderBuilder::new().from_reader(data);
+        let mut errors = Vec::new();
+        loop {
+            let mut raw = ByteRecord::new();
+            match reader.read_byte_record(&mut raw) {
+                Ok(false) => break,
+                Ok(true) => {
+                    if let Err(e) = StringRecord::from_byte_record(raw) {
+                        errors.push(BadRow::from_utf8(e));
+                    }
+                }
+                Err(e) => errors.push(BadRow::from_csv(e, &raw)),
+            }
+        }
+        errors
+    }
+
+    #[test]
+    fn test_bad_row_positions() {
+        let errors = bad_rows(b"a,b\n1,2\n3\n4,\xff\n5,6\n");
+        let messages: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+        assert_eq!(
+            messages,
+            vec![
+                "line 3, byte 8: expected 2 fields, found 1",
+                "line 4, byte 10: invalid UTF-8 in field 2",
+            ]
+        );
+        let bad = errors[1].downcast_ref::<BadRow>().unwrap();
+        assert_eq!(bad.fields, vec!["4", "\u{FFFD}"]);
+    }
+
+    #[test]
+    fn test_row_errors_modes() {
+        let headers = StringRecord::from(vec!["a", "b"]);
+        let opts = |on_error, quarantine: Option<&str>| CsvErrorOpts {
+            on_error,
+            quarantine: quarantine.map(String::from),
+        };
+
+        let mut fail = RowErrors::new(&opts(OnError::Fail, None), &headers).unwrap();
+        let err = fail.handle(bad_rows(b"a,b\n3\n").remove(0)).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "line 2, byte 4: expected 2 fields, found 1"
+        );
+        assert!(fail.handle(anyhow::anyhow!("disk full")).is_err());
+
+        let mut skip = RowErrors::new(&opts(OnError::Skip, None), &headers).unwrap();
+        skip.handle(bad_rows(b"a,b\n3\n").remove(0)).unwrap();
+        assert!(skip.handle(anyhow::anyhow!("disk full")).is_err());
+        assert_eq!(skip.rejected, 1);
+
+        let file = NamedTempFile::new().unwrap();
+        let path = file.path().to_str().unwrap();
+        let mut quarantine =
+            RowErrors::new(&opts(OnError::Quarantine, Some(path)), &headers).unwrap();
+        for err in bad_rows(b"a,b\n1,2\n3\n4,5,\"x,y\"\n") {
+            quarantine.handle(err).unwrap();
+        }
+        quarantine.finish(3, 0, 1, false).unwrap();
+        assert_eq!(
+            fs::read_to_string(path).unwrap(),
+            "line,byte,reason,a,b\n\
+             3,8,\"expected 2 fields, found 1\",3\n\
+             4,10,\"expected 2 fields, found 3\",4,5,\"x,y\"\n"
+        );
+        assert!(RowErrors::new(&opts(OnError::Quarantine, None), &headers).is_err());
+    }
+}
@@ -0,0 +1,170 @@
+use std::{
+    io::{Cursor, Read},
+    path::Path,
+};
+
+use anyhow::{anyhow, Result};
+use calamine::{open_workbook_auto, Data, DataType, Range, Reader};
+use chrono::Timelike;
+use csv::WriterBuilder;
+
+use crate::cli::CsvReaderOpts;
+
+const EXTENSIONS: [&str; 6] = ["xlsx", "xlsm", "xlsb", "xls", "xla", "ods"];
+
+// 根据扩展名判断, 标准输入总是当作 csv
+pub(crate) fn is_spreadsheet(input: &str) -> bool {
+    Path::new(input)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+}
+
+// 把工作表中的区域转换成 csv, 之后与普通 csv 输入走同样的流程
+pub(crate) fn read_sheet(input: &str, opts: &CsvReaderOpts) -> Result<Box<dyn Read>> {
+    let mut workbook = open_workbook_auto(input)?;
+    let names = workbook.sheet_names();
+    let name = match &opts.sheet {
+        None => names.first(),
+        Some(sheet) => names.iter().find(|n| *n == sheet).or_else(|| {
+            sheet
+                .parse::<usize>()
+                .ok()
+                .and_then(|i| i.checked_sub(1))
+                .and_then(|i| names.get(i))
+        }),
+    };
+    let name = name
+        .ok_or_else(|| match &opts.sheet {
+            Some(sheet) => anyhow!(
+                "Sheet '{}' not found, available: {}",
+                sheet,
+                names.join(", ")
+            ),
+            None => anyhow!("No sheet found in {}", input),
+        })?
+        .clone();
+    if opts.verbose {
+        eprintln!("Sheet: {}", name);
+    }
+    let range = select(workbook.worksheet_range(&name)?, opts);
+
+    let mut writer = WriterBuilder::new()
+        .delimiter(opts.delimiter)
+        .quote(opts.quote)
+        .from_writer(Vec::new());
+    for row in range.rows() {
+        writer.write_record(row.iter().map(cell_text))?;
+    }
+    let data = writer
+        .into_inner()
+        .map_err(|e| anyhow!("Failed to convert sheet {}: {}", name, e))?;
+    Ok(Box::new(Cursor::new(data)))
+}
+
+// 工作表自己的区域从第一个非空单元格开始, --range 使用的是绝对位置
+fn select(range: Range<Data>, opts: &CsvReaderOpts) -> Range<Data> {
+    match (opts.range, range.end()) {
+        (Some(cells), Some(last)) => range.range(cells.start, cells.end.unwrap_or(last)),
+        (Some(cells), None) => Range::new(cells.start, cells.end.unwrap_or(cells.start)),
+        (None, _) => range,
+    }
+}
+
+fn cell_text(cell: &Data) -> String {
+    match cell {
+        Data::Empty => String::new(),
+        Data::String(s) | Data::DateTimeIso(s) | Data::DurationIso(s) => s.clone(),
+        Data::Int(i) => i.to_string(),
+        // 表格里的数字都是浮点数, 整数不要输出成 10.0
+        Data::Float(f) if f.fract() == 0.0 && f.abs() < 1e15 => (*f as i64).to_string(),
+        Data::Float(f) => f.to_string(),
+        Data::Bool(b) => b.to_string(),
+        Data::DateTime(dt) if dt.is_datetime() => match cell.as_datetime() {
+            Some(dt) if dt.num_seconds_from_midnight() == 0 => dt.format("%Y-%m-%d").to_string(),
+            Some(dt) => dt.format("%Y-%m-%d %H:%M:%S").to_string(),
+            None => dt.as_f64().to_string(),
+        },
+        Data::DateTime(dt) => match dt.as_duration() {
+            Some(d) => format!(
+                "{}:{:02}:{:02}",
+                d.num_hours(),
+                d.num_minutes() % 60,
+                d.num_seconds() % 60
+            ),
+            None => dt.as_f64().to_string(),
+        },
+        Data::Error(e) => e.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::CellRange;
+
+    fn read(input: &str, opts: &CsvReaderOpts) -> String {
+        let mut out = String::new();
+        read_sheet(input, opts)
+            .unwrap()
+            .read_to_string(&mut out)
+            .unwrap();
+        out
+    }
+
+    #[test]
+    fn test_read_sheet_range() {
+        assert!(is_spreadsheet("assets/Juventus.XLSX"));
+        assert!(!is_spreadsheet("assets/juventus.csv"));
+
+        let csv = std::fs::read_to_string("assets/juventus.csv").unwrap();
+        let opts = CsvReaderOpts {
+            range: Some(CellRange {
+                start: (2, 1),
+                end: None,
+            }),
+            ..Default::default()
+        };
+        assert_eq!(read("assets/juventus.xlsx", &opts), csv);
+        assert_eq!(read("assets/juventus.ods", &CsvReaderOpts::default()), csv);
+
+        let opts = CsvReaderOpts {
+            range: Some(CellRange {
+                start: (2, 1),
+                end: Some((3, 2)),
+            }),
+            ..Default::default()
+        };
+        assert_eq!(
+            read("assets/juventus.xlsx", &opts),
+            "Name,Position\nWojciech Szczesny,Goalkeeper\n"
+        );
+    }
+
+    #[test]
+    fn test_read_sheet_select() {
+        let opts = CsvReaderOpts {
+            sheet: Some("Staff".to_string()),
+            delimiter: b';',
+            ..Default::default()
+        };
+        let staff = "Name;Role;Since;Active\nMaurizio Sarri;Head Coach;2019-06-19;true\n";
+        assert_eq!(read("assets/juventus.xlsx", &opts), staff);
+        let opts = CsvReaderOpts {
+            sheet: Some("2".to_string()),
+            delimiter: b';',
+            ..Default::default()
+        };
+        assert_eq!(read("assets/juventus.xlsx", &opts), staff);
+
+        let opts = CsvReaderOpts {
+            sheet: Some("Coaches".to_string()),
+            ..Default::default()
+        };
+        let err = read_sheet("assets/juventus.xlsx", &opts).err().unwrap();
+        assert_eq!(
+            err.to_string(),
+            "Sheet 'Coaches' not found, available: Players, Staff"
+        );
+    }
+}
@@ -0,0 +1,304 @@
+use std::{collections::HashMap, fmt, io::Read};
+
+use anyhow::Result;
+use csv::{ReaderBuilder, StringRecord};
+use serde_json::Value;
+
+use super::{csv_decode::peek, csv_infer::infer_cell};
+use crate::cli::CsvReaderOpts;
+
+// 嗅探时最多读取的字节数
+const SAMPLE_SIZE: usize = 64 * 1024;
+// 最多分析的记录数
+const SAMPLE_ROWS: usize = 1000;
+const DELIMITERS: [u8; 4] = [b',', b';', b'\t', b'|'];
+const QUOTES: [u8; 2] = [b'"', b'\''];
+const BOM: &[u8] = b"\xEF\xBB\xBF";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Quoting {
+    // 没有字段被引号包围
+    Never,
+    // 只有部分字段 (通常是包含分隔符的) 被引号包围
+    Minimal,
+    Always,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum LineTerminator {
+    Lf,
+    CrLf,
+    Cr,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Dialect {
+    pub delimiter: u8,
+    pub quote: u8,
+    pub quoting: Quoting,
+    pub header: bool,
+    pub terminator: LineTerminator,
+    pub bom: bool,
+}
+
+// 读取一段样本进行嗅探, 返回的 reader 仍然从头开始
+pub(crate) fn sniff_reader(input: Box<dyn Read>) -> Result<(Box<dyn Read>, Dialect)> {
+    let (sample, input) = peek(input, SAMPLE_SIZE)?;
+    let dialect = sniff(&sample, sample.len() == SAMPLE_SIZE);
+    Ok((input, dialect))
+}
+
+pub(crate) fn sniff(sample: &[u8], truncated: bool) -> Dialect {
+    let bom = sample.starts_with(BOM);
+    let mut data = if bom { &sample[BOM.len()..] } else { sample };
+    // 样本被截断时最后一行可能不完整
+    if truncated {
+        if let Some(pos) = data.iter().rposition(|b| *b == b'\n') {
+            data = &data[..=pos];
+        }
+    }
+
+    let quote = sniff_quote(data);
+    let delimiter = sniff_delimiter(data, quote);
+    let rows = parse(data, delimiter, quote);
+    let fields: usize = rows.iter().map(StringRecord::len).sum();
+    let quoting = match count_quoted(data, delimiter, quote) {
+        0 => Quoting::Never,
+        n if n >= fields => Quoting::Always,
+        _ => Quoting::Minimal,
+    };
+    Dialect {
+        delimiter,
+        quote,
+        quoting,
+        header: sniff_header(&rows),
+        terminator: sniff_terminator(data),
+        bom,
+    }
+}
+
+impl Dialect {
+    // 嗅探结果覆盖 --delimiter/--quote/--header
+    pub(crate) fn apply(&self, opts: &mut CsvReaderOpts) {
+        opts.delimiter = self.delimiter;
+        opts.quote = self.quote;
+        opts.header = self.header;
+    }
+}
+
+// 统计出现在行首或分隔符之后的引号, 出现更多的那个就是引号字符
+fn sniff_quote(data: &[u8]) -> u8 {
+    let counts: Vec<usize> = QUOTES
+        .iter()
+        .map(|q| {
+            DELIMITERS
+                .iter()
+                .map(|d| count_quoted(data, *d, *q))
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+    if counts[1] > counts[0] {
+        QUOTES[1]
+    } else {
+        QUOTES[0]
+    }
+}
+
+// 每行列数最一致的候选分隔符胜出, 一致程度相同时列数多的优先
+fn sniff_delimiter(data: &[u8], quote: u8) -> u8 {
+    let mut best = (b',', 0.0, 0);
+    for delimiter in DELIMITERS {
+        let rows = parse(data, delimiter, quote);
+        let mut freq: HashMap<usize, usize> = HashMap::new();
+        for row in &rows {
+            *freq.entry(row.len()).or_default() += 1;
+        }
+        let Some((&mode, &count)) = freq.iter().max_by_key(|(len, count)| (**count, **len)) else {
+            continue;
+        };
+        if mode < 2 {
+            continue;
+        }
+        let consistency = count as f64 / rows.len() as f64;
+        if consistency > best.1 || (consistency == best.1 && mode > best.2) {
+            best = (delimiter, consistency, mode);
+        }
+    }
+    best.0
+}
+
+// 参考 Python csv.Sniffer: 某列除首行外都是数字而首行不是, 或者长度都相同而首行不同, 则首行像表头
+fn sniff_header(rows: &[StringRecord]) -> bool {
+    let Some((first, rest)) = rows.split_first() else {
+        return true;
+    };
+    if rest.is_empty() {
+        return true;
+    }
+    let mut score = 0;
+    for (i, header) in first.iter().enumerate() {
+        let cells: Vec<&str> = rest
+            .iter()
+            .filter_map(|r| r.get(i))
+            .filter(|c| !c.is_empty())
+            .collect();
+        if cells.is_empty() {
+            continue;
+        }
+        if cells.iter().all(|c| is_typed(c)) {
+            score += if is_typed(header) { -1 } else { 1 };
+        } else if cells.iter().all(|c| c.len() == cells[0].len()) {
+            score += if header.len() == cells[0].len() {
+                -1
+            } else {
+                1
+            };
+        }
+    }
+    score >= 0
+}
+
+fn sniff_terminator(data: &[u8]) -> LineTerminator {
+    let crlf = data.windows(2).filter(|w| w == b"\r\n").count();
+    let lf = data.iter().filter(|b| **b == b'\n').count() - crlf;
+    let cr = data.iter().filter(|b| **b == b'\r').count() - crlf;
+    if crlf > 0 && crlf >= lf && crlf >= cr {
+        LineTerminator::CrLf
+    } else if cr > lf {
+        LineTerminator::Cr
+    } else {
+        LineTerminator::Lf
+    }
+}
+
+fn is_typed(cell: &str) -> bool {
+    matches!(infer_cell(cell), Value::Number(_) | Value::Bool(_))
+}
+
+fn parse(data: &[u8], delimiter: u8, quote: u8) -> Vec<StringRecord> {
+    ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .delimiter(delimiter)
+        .quote(quote)
+        .from_reader(data)
+        .records()
+        .take(SAMPLE_ROWS)
+        .map_while(Result::ok)
+        .collect()
+}
+
+fn count_quoted(data: &[u8], delimiter: u8, quote: u8) -> usize {
+    let mut prev = b'\n';
+    let mut count = 0;
+    for &b in data {
+        if b == quote && (matches!(prev, b'\n' | b'\r') || prev == delimiter) {
+            count += 1;
+        }
+        prev = b;
+    }
+    count
+}
+
+impl fmt::Display for Dialect {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "delimiter: '{}', quote: '{}' ({}), header: {}, line terminator: {}, BOM: {}",
+            (self.delimiter as char).escape_default(),
+            self.quote as char,
+            self.quoting,
+            if self.header { "yes" } else { "no" },
+            self.terminator,
+            if self.bom { "yes" } else { "no" },
+        )
+    }
+}
+
+impl From<Quoting> for &'static str {
+    fn from(quoting: Quoting) -> Self {
+        match quoting {
+            Quoting::Never => "never",
+            Quoting::Minimal => "minimal",
+            Quoting::Always => "always",
+        }
+    }
+}
+
+impl fmt::Display for Quoting {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
+
+impl From<LineTerminator> for &'static str {
+    fn from(terminator: LineTerminator) -> Self {
+        match terminator {
+            LineTerminator::Lf => "LF",
+            LineTerminator::CrLf => "CRLF",
+            LineTerminator::Cr => "CR",
+        }
+    }
+}
+
+impl fmt::Display for LineTerminator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    #[test]
+    fn test_sniff_semicolon_with_bom() {
+        let data = "\u{feff}Name;DOB;Kit Number\r\n\
+            Wojciech Szczesny;\"Apr 18, 1990 (29)\";1\r\n\
+            Mattia Perin;\"Nov 10, 1992 (26)\";37\r\n";
+        let dialect = sniff(data.as_bytes(), false);
+        assert_eq!(
+            dialect,
+            Dialect {
+                delimiter: b';',
+                quote: b'"',
+                quoting: Quoting::Minimal,
+                header: true,
+                terminator: LineTerminator::CrLf,
+                bom: true,
+            }
+        );
+        assert_eq!(
+            dialect.to_string(),
+            "delimiter: ';', quote: '\"' (minimal), header: yes, line terminator: CRLF, BOM: yes"
+        );
+    }
+
+    #[test]
+    fn test_sniff_tab_pipe_and_header() {
+        let data = "Buffon\t77\t1978\nPerin\t37\t1992\n";
+        let dialect = sniff(data.as_bytes(), false);
+        assert_eq!(dialect.delimiter, b'\t');
+        assert!(!dialect.header);
+        assert_eq!(dialect.quoting, Quoting::Never);
+
+        let data = "'name'|'kit'\n'Buffon'|'77'\n'Perin'|'37'\n";
+        let dialect = sniff(data.as_bytes(), false);
+        assert_eq!((dialect.delimiter, dialect.quote), (b'|', b'\''));
+        assert_eq!(dialect.quoting, Quoting::Always);
+        assert!(dialect.header);
+    }
+
+    #[test]
+    fn test_sniff_reader_replays_sample() {
+        let input: Box<dyn Read> = Box::new(Cursor::new(b"a|b\n1|2\n".to_vec()));
+        let (mut reader, dialect) = sniff_reader(input).unwrap();
+        let mut content = String::new();
+        reader.read_to_string(&mut content).unwrap();
+        assert_eq!(content, "a|b\n1|2\n");
+        assert_eq!(dialect.delimiter, b'|');
+    }
+}
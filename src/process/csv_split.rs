@@ -0,0 +1,430 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fs::{self, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Result};
+use csv::{StringRecord, Writer};
+
+use super::{
+    csv_convert::CsvRows,
+    csv_ops::{column_indices, csv_writer, csv_writer_builder},
+};
+use crate::cli::{CsvFilterOpts, CsvReaderOpts};
+
+// 按某一列的取值拆分时, 空值写到这个文件
+const EMPTY_VALUE: &str = "empty";
+
+// 同时打开的文件数上限, 超出时关闭最久没有写入的文件, 之后再以追加方式打开
+const MAX_OPEN_FILES: usize = 128;
+
+// 一个拆分出的文件, 以及已写入的行数; 被关闭时 writer 为 None
+struct Part {
+    path: PathBuf,
+    writer: Option<Writer<Box<dyn Write + Send>>>,
+    rows: usize,
+    used: u64,
+}
+
+// 所有拆分出的文件, 按最近写入的顺序限制打开的文件数
+struct Parts<'a> {
+    parts: Vec<Part>,
+    lru: BTreeMap<u64, usize>,
+    tick: u64,
+    max_open: usize,
+    opts: &'a CsvReaderOpts,
+    headers: &'a StringRecord,
+}
+
+// 每个文件都带上表头, 输出的文件名和行数打印到 stdout, 方便后续脚本处理
+pub fn process_csv_split(
+    input: &str,
+    output_dir: &str,
+    prefix: Option<&str>,
+    rows_per_file: Option<usize>,
+    by_column: Option<&str>,
+    opts: &CsvReaderOpts,
+) -> Result<()> {
+    split(
+        input,
+        output_dir,
+        prefix,
+        (rows_per_file, by_column),
+        opts,
+        MAX_OPEN_FILES,
+    )
+}
+
+fn split(
+    input: &str,
+    output_dir: &str,
+    prefix: Option<&str>,
+    by: (Option<usize>, Option<&str>),
+    opts: &CsvReaderOpts,
+    max_open: usize,
+) -> Result<()> {
+    let mut rows = CsvRows::open(input, opts, &CsvFilterOpts::default())?;
+    let headers = rows.headers().clone();
+    let dialect = rows.dialect().clone();
+    fs::create_dir_all(output_dir)?;
+    let prefix = prefix
+        .map(String::from)
+        .unwrap_or_else(|| default_prefix(input));
+    let dir = Path::new(output_dir);
+
+    let mut parts = Parts::new(&dialect, &headers, max_open);
+    let result = (|| -> Result<()> {
+        match by {
+            (Some(0), _) => Err(anyhow!("--rows must be at least 1")),
+            (Some(limit), None) => {
+                for record in rows.by_ref() {
+                    let record = record?;
+                    let full = parts.parts.last().is_none_or(|p| p.rows >= limit);
+                    if full {
+                        // 写满的文件不会再用到, 立即关闭
+                        if let Some(last) = parts.parts.len().checked_sub(1) {
+                            parts.close(last)?;
+                        }
+                        let name = format!("{}_{:04}.csv", prefix, parts.parts.len() + 1);
+                        parts.create(dir.join(name))?;
+                    }
+                    parts.write(parts.parts.len() - 1, &record)?;
+                }
+                Ok(())
+            }
+            (None, Some(column)) => {
+                let idx = column_indices(&headers, &[column.to_string()])?[0];
+                let mut by_value: HashMap<String, usize> = HashMap::new();
+                let mut names = HashSet::new();
+                for record in rows.by_ref() {
+                    let record = record?;
+                    let value = record.get(idx).unwrap_or("");
+                    let part = match by_value.get(value) {
+                        Some(&part) => part,
+                        None => {
+                            let name = unique_name(&mut names, &prefix, value);
+                            let part = parts.create(dir.join(name))?;
+                            by_value.insert(value.to_string(), part);
+                            part
+                        }
+                    };
+                    parts.write(part, &record)?;
+                }
+                Ok(())
+            }
+            _ => Err(anyhow!("Specify exactly one of --rows and --by-column")),
+        }
+    })();
+
+    // 出错时删除已经写出的文件, 避免留下不完整的结果
+    let parts = match result.and_then(|_| parts.finish()) {
+        Ok(parts) => parts,
+        Err(e) => {
+            parts.remove_all();
+            return Err(e);
+        }
+    };
+    for part in parts {
+        println!("{}\t{}", part.path.display(), part.rows);
+    }
+    Ok(())
+}
+
+// 按列名对齐: 输出的表头是所有输入表头的并集, 按第一次出现的顺序; 缺少的列留空
+pub fn process_csv_cat(inputs: &[String], output: &str, opts: &CsvReaderOpts) -> Result<()> {
+    if inputs.iter().filter(|i| *i == "-").count() > 1 {
+        return Err(anyhow!("stdin can only be used once"));
+    }
+    let mut sources = inputs
+        .iter()
+        .map(|input| CsvRows::open(input, opts, &CsvFilterOpts::default()))
+        .collect::<Result<Vec<_>>>()?;
+
+    // 输出使用第一个输入的方言; 有输入没有表头时无法按列名对齐, 直接依次拼接
+    let mut dialect = match sources.first() {
+        Some(rows) => rows.dialect().clone(),
+        None => opts.clone(),
+    };
+    if !sources.iter().all(|rows| rows.dialect().header) {
+        dialect.header = false;
+        let mut writer = csv_writer(output, &dialect, &StringRecord::new())?;
+        for rows in &mut sources {
+            for record in rows.by_ref() {
+                writer.write_record(&record?)?;
+            }
+        }
+        writer.flush()?;
+        return Ok(());
+    }
+
+    let mut headers = StringRecord::new();
+    let mut mappings = Vec::with_capacity(sources.len());
+    for rows in &sources {
+        mappings.push(align_headers(&mut headers, rows.headers()));
+    }
+    if opts.verbose {
+        for (input, mapping) in inputs.iter().zip(&mappings) {
+            if mapping.len() < headers.len() {
+                eprintln!(
+                    "{}: {} of {} columns missing, left empty",
+                    input,
+                    headers.len() - mapping.len(),
+                    headers.len()
+                );
+            }
+        }
+    }
+
+    let mut writer = csv_writer(output, &dialect, &headers)?;
+    for ((input, rows), mapping) in inputs.iter().zip(&mut sources).zip(&mappings) {
+        let mut fields = vec![String::new(); headers.len()];
+        while let Some(record) = rows.next() {
+            let record = record?;
+            if record.len() > mapping.len() {
+                return Err(anyhow!(
+                    "{}: line {} has more fields than the header",
+                    input,
+                    rows.line()
+                ));
+            }
+            fields.iter_mut().for_each(String::clear);
+            for (value, &i) in record.iter().zip(mapping) {
+                fields[i].push_str(value);
+            }
+            writer.write_record(&fields)?;
+        }
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+impl<'a> Parts<'a> {
+    fn new(opts: &'a CsvReaderOpts, headers: &'a StringRecord, max_open: usize) -> Self {
+        Self {
+            parts: Vec::new(),
+            lru: BTreeMap::new(),
+            tick: 0,
+            max_open: max_open.max(1),
+            opts,
+            headers,
+        }
+    }
+
+    // 新建文件并写入表头, 返回它的下标
+    fn create(&mut self, path: PathBuf) -> Result<usize> {
+        let output = path
+            .to_str()
+            .ok_or_else(|| anyhow!("Invalid output path: {}", path.display()))?;
+        let writer = csv_writer(output, self.opts, self.headers)?;
+        self.parts.push(Part {
+            path,
+            writer: None,
+            rows: 0,
+            used: 0,
+        });
+        let idx = self.parts.len() - 1;
+        self.open(idx, writer)?;
+        Ok(idx)
+    }
+
+    fn write(&mut self, idx: usize, record: &StringRecord) -> Result<()> {
+        if self.parts[idx].writer.is_none() {
+            let file = OpenOptions::new()
+                .append(true)
+                .open(&self.parts[idx].path)?;
+            let writer = csv_writer_builder(self.opts).from_writer(Box::new(file) as Box<_>);
+            self.open(idx, writer)?;
+        } else {
+            self.touch(idx);
+        }
+        let part = &mut self.parts[idx];
+        part.writer
+            .as_mut()
+            .expect("opened above")
+            .write_record(record)?;
+        part.rows += 1;
+        Ok(())
+    }
+
+    fn open(&mut self, idx: usize, writer: Writer<Box<dyn Write + Send>>) -> Result<()> {
+        while self.lru.len() >= self.max_open {
+            let (_, oldest) = self.lru.pop_first().expect("lru is not empty");
+            self.close(oldest)?;
+        }
+        self.parts[idx].writer = Some(writer);
+        self.touch(idx);
+        Ok(())
+    }
+
+    fn touch(&mut self, idx: usize) {
+        let part = &mut self.parts[idx];
+        self.lru.remove(&part.used);
+        self.tick += 1;
+        part.used = self.tick;
+        self.lru.insert(part.used, idx);
+    }
+
+    fn close(&mut self, idx: usize) -> Result<()> {
+        let part = &mut self.parts[idx];
+        self.lru.remove(&part.used);
+        if let Some(mut writer) = part.writer.take() {
+            writer.flush()?;
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<Vec<Part>> {
+        for idx in 0..self.parts.len() {
+            self.close(idx)?;
+        }
+        Ok(std::mem::take(&mut self.parts))
+    }
+
+    fn remove_all(self) {
+        for part in self.parts {
+            drop(part.writer);
+            let _ = fs::remove_file(&part.path);
+        }
+    }
+}
+
+// 把文件的表头合并进 merged, 返回每一列在 merged 中的位置; 重名的列按出现次序分别对应
+fn align_headers(merged: &mut StringRecord, headers: &StringRecord) -> Vec<usize> {
+    let mut seen: HashMap<&str, usize> = HashMap::new();
+    let mut mapping = Vec::with_capacity(headers.len());
+    for name in headers {
+        let nth = seen.entry(name).or_default();
+        let found = merged
+            .iter()
+            .enumerate()
+            .filter(|(_, h)| *h == name)
+            .nth(*nth)
+            .map(|(i, _)| i);
+        *nth += 1;
+        mapping.push(found.unwrap_or_else(|| {
+            merged.push_field(name);
+            merged.len() - 1
+        }));
+    }
+    mapping
+}
+
+fn default_prefix(input: &str) -> String {
+    Path::new(input)
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .filter(|_| input != "-")
+        .unwrap_or("part")
+        .to_string()
+}
+
+// 取值转换成安全的文件名; 不同取值转换后重名时加上序号区分
+fn unique_name(names: &mut HashSet<String>, prefix: &str, value: &str) -> String {
+    let value = value.trim();
+    let stem: String = if value.is_empty() {
+        EMPTY_VALUE.to_string()
+    } else {
+        value
+            .chars()
+            .map(|c| match c {
+                c if c.is_alphanumeric() || c == '-' || c == '.' => c,
+                _ => '_',
+            })
+            .collect()
+    };
+    let mut name = format!("{}_{}.csv", prefix, stem);
+    let mut n = 1;
+    while !names.insert(name.clone()) {
+        n += 1;
+        name = format!("{}_{}_{}.csv", prefix, stem, n);
+    }
+    name
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::{tempdir, NamedTempFile};
+
+    use super::{super::test_utils::temp_file, *};
+
+    #[test]
+    fn test_csv_split_rows() {
+        let dir = tempdir().unwrap();
+        let out = dir.path().to_str().unwrap();
+        let opts = CsvReaderOpts::default();
+        process_csv_split("assets/juventus.csv", out, None, Some(10), None, &opts).unwrap();
+        let read = |name: &str| fs::read_to_string(dir.path().join(name)).unwrap();
+        let lines: Vec<usize> = [
+            "juventus_0001.csv",
+            "juventus_0002.csv",
+            "juventus_0003.csv",
+        ]
+        .iter()
+        .map(|name| read(name).lines().count())
+        .collect();
+        assert_eq!(lines, vec![11, 11, 8]);
+        assert!(!dir.path().join("juventus_0004.csv").exists());
+        assert!(read("juventus_0003.csv").starts_with("Name,Position,DOB,Nationality,Kit Number\n"));
+    }
+
+    #[test]
+    fn test_csv_split_by_column() {
+        let input = temp_file("id,team\n1,A/B\n2,\n3,A_B\n4,A/B\n");
+        let dir = tempdir().unwrap();
+        let opts = CsvReaderOpts::default();
+        let (input, out) = (input.path().to_str().unwrap(), dir.path().to_str().unwrap());
+        process_csv_split(input, out, Some("t"), None, Some("team"), &opts).unwrap();
+        let read = |name: &str| fs::read_to_string(dir.path().join(name)).unwrap();
+        assert_eq!(read("t_A_B.csv"), "id,team\n1,A/B\n4,A/B\n");
+        assert_eq!(read("t_empty.csv"), "id,team\n2,\n");
+        assert_eq!(read("t_A_B_2.csv"), "id,team\n3,A_B\n");
+
+        let err = process_csv_split(input, out, None, None, Some("Team"), &opts).unwrap_err();
+        assert_eq!(err.to_string(), "Unknown column: Team");
+    }
+
+    #[test]
+    fn test_csv_split_limits_open_files() {
+        let content: String = (0..20).map(|i| format!("{},{}\n", i, i % 5)).collect();
+        let input = temp_file(format!("id,group\n{}", content));
+        let dir = tempdir().unwrap();
+        let opts = CsvReaderOpts::default();
+        let (input, out) = (input.path().to_str().unwrap(), dir.path().to_str().unwrap());
+        split(input, out, Some("g"), (None, Some("group")), &opts, 2).unwrap();
+        let read = |name: &str| fs::read_to_string(dir.path().join(name)).unwrap();
+        // 被关闭后再追加的文件只有一行表头
+        assert_eq!(read("g_3.csv"), "id,group\n3,3\n8,3\n13,3\n18,3\n");
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 5);
+
+        // 出错时不留下不完整的文件
+        let bad = temp_file("id,group\n1,a\n2,b\n3\n");
+        let dir = tempdir().unwrap();
+        let (input, out) = (bad.path().to_str().unwrap(), dir.path().to_str().unwrap());
+        assert!(split(input, out, Some("g"), (None, Some("group")), &opts, 1).is_err());
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn test_csv_cat_aligns_headers() {
+        let a = temp_file("id,name\n1,Buffon\n");
+        let b = temp_file("name,kit,id\nDybala,10,2\n");
+        let out = NamedTempFile::new().unwrap();
+        let inputs = vec![
+            a.path().to_str().unwrap().to_string(),
+            b.path().to_str().unwrap().to_string(),
+        ];
+        let output = out.path().to_str().unwrap();
+        process_csv_cat(&inputs, output, &CsvReaderOpts::default()).unwrap();
+        assert_eq!(
+            fs::read_to_string(output).unwrap(),
+            "id,name,kit\n1,Buffon,\n2,Dybala,10\n"
+        );
+
+        let mut merged = StringRecord::from(vec!["a", "b", "a"]);
+        let mapping = align_headers(&mut merged, &StringRecord::from(vec!["a", "a", "a"]));
+        assert_eq!(mapping, vec![0, 2, 3]);
+        assert_eq!(merged, StringRecord::from(vec!["a", "b", "a", "a"]));
+    }
+}
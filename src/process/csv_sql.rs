@@ -0,0 +1,441 @@
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, BufWriter, Seek, SeekFrom, Write},
+};
+
+use anyhow::{anyhow, Result};
+use rusqlite::{types::Value as SqliteValue, Connection};
+use serde_json::Value;
+
+use super::csv_writer::{unique_columns, RecordWriter};
+use crate::cli::{CsvOutputOpts, SqlDialect};
+
+// 列类型由所有记录共同决定, 出现冲突时退化成 TEXT
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum SqlKind {
+    Null,
+    Integer,
+    Real,
+    Boolean,
+    Text,
+}
+
+// 建表前需要看完所有记录才能确定列类型, 记录先按列顺序暂存到临时文件
+pub(crate) struct Stage {
+    file: BufWriter<File>,
+    pub(crate) columns: Vec<String>,
+    pub(crate) kinds: Vec<SqlKind>,
+}
+
+pub(crate) struct SqlWriter<W: Write> {
+    writer: W,
+    table: String,
+    dialect: SqlDialect,
+    batch: usize,
+    stage: Option<Stage>,
+}
+
+// 暂存记录, 最后一次性导入到 SQLite 的某个表中
+pub(crate) struct SqliteTable {
+    table: String,
+    batch: usize,
+    stage: Option<Stage>,
+}
+
+pub(crate) struct SqliteWriter {
+    path: String,
+    table: SqliteTable,
+}
+
+pub(crate) fn sql_writer<W: Write>(writer: W, opts: &CsvOutputOpts) -> SqlWriter<W> {
+    SqlWriter {
+        writer,
+        table: table_name(opts),
+        dialect: opts.sql_dialect,
+        batch: opts.batch_size.max(1),
+        stage: None,
+    }
+}
+
+pub(crate) fn sqlite_writer(path: &str, opts: &CsvOutputOpts) -> Result<SqliteWriter> {
+    if path == "-" {
+        return Err(anyhow!("SQLite output needs a database file, use -o"));
+    }
+    Ok(SqliteWriter {
+        path: path.to_string(),
+        table: SqliteTable::new(&table_name(opts), opts.batch_size, None)?,
+    })
+}
+
+impl<W: Write> RecordWriter for SqlWriter<W> {
+    fn write(&mut self, record: Value) -> Result<()> {
+        Stage::push(&mut self.stage, record)
+    }
+
+    fn columns(&mut self, columns: &[String]) -> Result<()> {
+        Stage::declare(&mut self.stage, columns)
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        let Some(stage) = self.stage.take() else {
+            return Ok(());
+        };
+        let dialect = self.dialect;
+        let (columns, kinds) = (stage.columns.clone(), stage.kinds.clone());
+        writeln!(
+            self.writer,
+            "{};\n",
+            create_table(dialect, &self.table, &columns, &kinds)
+        )?;
+        let names: Vec<String> = columns.iter().map(|c| quote_ident(dialect, c)).collect();
+        let insert = format!(
+            "INSERT INTO {} ({}) VALUES",
+            quote_ident(dialect, &self.table),
+            names.join(", ")
+        );
+
+        // 每批一个事务和一条多行 INSERT
+        let mut rows = stage.rows()?.peekable();
+        while rows.peek().is_some() {
+            let mut values = Vec::with_capacity(self.batch);
+            for row in rows.by_ref().take(self.batch) {
+                let row: Vec<String> = row?
+                    .iter()
+                    .zip(&kinds)
+                    .map(|(v, kind)| sql_literal(dialect, v, *kind))
+                    .collect();
+                values.push(format!("  ({})", row.join(", ")));
+            }
+            writeln!(self.writer, "{}", dialect.begin())?;
+            writeln!(self.writer, "{}\n{};", insert, values.join(",\n"))?;
+            writeln!(self.writer, "COMMIT;")?;
+        }
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+impl RecordWriter for SqliteWriter {
+    fn write(&mut self, record: Value) -> Result<()> {
+        self.table.push(record)
+    }
+
+    fn columns(&mut self, columns: &[String]) -> Result<()> {
+        Stage::declare(&mut self.table.stage, columns)
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        let mut conn = Connection::open(&self.path)?;
+        self.table.load(&mut conn)
+    }
+}
+
+impl SqliteTable {
+    // 传入 columns 时即使没有任何记录也会建表
+    pub(crate) fn new(table: &str, batch: usize, columns: Option<Vec<String>>) -> Result<Self> {
+        Ok(Self {
+            table: table.to_string(),
+            batch: batch.max(1),
+            stage: columns.map(Stage::new).transpose()?,
+        })
+    }
+
+    pub(crate) fn push(&mut self, record: Value) -> Result<()> {
+        Stage::push(&mut self.stage, record)
+    }
+
+    pub(crate) fn load(&mut self, conn: &mut Connection) -> Result<()> {
+        let Some(stage) = self.stage.take() else {
+            return Ok(());
+        };
+        let dialect = SqlDialect::Sqlite;
+        let (columns, kinds) = (stage.columns.clone(), stage.kinds.clone());
+        conn.execute_batch(&create_table(dialect, &self.table, &columns, &kinds))?;
+        let insert = format!(
+            "INSERT INTO {} VALUES ({})",
+            quote_ident(dialect, &self.table),
+            vec!["?"; columns.len()].join(", ")
+        );
+
+        let mut rows = stage.rows()?.peekable();
+        while rows.peek().is_some() {
+            let tx = conn.transaction()?;
+            {
+                let mut stmt = tx.prepare_cached(&insert)?;
+                for row in rows.by_ref().take(self.batch) {
+                    let params: Vec<SqliteValue> = row?
+                        .iter()
+                        .zip(&kinds)
+                        .map(|(v, kind)| sqlite_value(v, *kind))
+                        .collect();
+                    stmt.execute(rusqlite::params_from_iter(params))?;
+                }
+            }
+            tx.commit()?;
+        }
+        Ok(())
+    }
+}
+
+impl Stage {
+    pub(crate) fn new(columns: Vec<String>) -> Result<Self> {
+        Ok(Stage {
+            file: BufWriter::new(tempfile::tempfile()?),
+            kinds: vec![SqlKind::Null; columns.len()],
+            columns,
+        })
+    }
+
+    // 还没有记录时按给定的列建立暂存, 列类型在没有值时默认为 TEXT
+    pub(crate) fn declare(stage: &mut Option<Stage>, columns: &[String]) -> Result<()> {
+        if stage.is_none() {
+            *stage = Some(Stage::new(unique_columns(columns))?);
+        }
+        Ok(())
+    }
+
+    pub(crate) fn push(stage: &mut Option<Stage>, record: Value) -> Result<()> {
+        let Value::Object(map) = record else {
+            return Err(anyhow!("Table output expects records to be objects"));
+        };
+        let stage = match stage {
+            Some(stage) => stage,
+            None => stage.insert(Stage::new(map.keys().cloned().collect())?),
+        };
+        let row: Vec<&Value> = stage
+            .columns
+            .iter()
+            .map(|c| map.get(c).unwrap_or(&Value::Null))
+            .collect();
+        for (kind, value) in stage.kinds.iter_mut().zip(&row) {
+            *kind = kind.merge(value);
+        }
+        serde_json::to_writer(&mut stage.file, &row)?;
+        stage.file.write_all(b"\n")?;
+        Ok(())
+    }
+
+    pub(crate) fn rows(self) -> Result<impl Iterator<Item = Result<Vec<Value>>>> {
+        let mut file = self
+            .file
+            .into_inner()
+            .map_err(|e| anyhow!("Failed to flush staged rows: {}", e))?;
+        file.seek(SeekFrom::Start(0))?;
+        Ok(BufReader::new(file)
+            .lines()
+            .map(|line| Ok(serde_json::from_str(&line?)?)))
+    }
+}
+
+impl SqlKind {
+    fn of(value: &Value) -> Self {
+        match value {
+            Value::Null => SqlKind::Null,
+            Value::Bool(_) => SqlKind::Boolean,
+            Value::Number(n) if n.is_i64() => SqlKind::Integer,
+            Value::Number(_) => SqlKind::Real,
+            _ => SqlKind::Text,
+        }
+    }
+
+    fn merge(self, value: &Value) -> Self {
+        match (self, SqlKind::of(value)) {
+            (kind, SqlKind::Null) | (SqlKind::Null, kind) => kind,
+            (a, b) if a == b => a,
+            (SqlKind::Integer, SqlKind::Real) | (SqlKind::Real, SqlKind::Integer) => SqlKind::Real,
+            _ => SqlKind::Text,
+        }
+    }
+
+    fn sql_type(self, dialect: SqlDialect) -> &'static str {
+        match (self, dialect) {
+            (SqlKind::Integer, SqlDialect::Sqlite) => "INTEGER",
+            (SqlKind::Integer, _) => "BIGINT",
+            (SqlKind::Real, SqlDialect::Sqlite) => "REAL",
+            (SqlKind::Real, SqlDialect::Postgres) => "DOUBLE PRECISION",
+            (SqlKind::Real, SqlDialect::Mysql) => "DOUBLE",
+            (SqlKind::Boolean, SqlDialect::Sqlite) => "INTEGER",
+            (SqlKind::Boolean, _) => "BOOLEAN",
+            // 全为空的列也用 TEXT
+            (SqlKind::Null | SqlKind::Text, _) => "TEXT",
+        }
+    }
+}
+
+impl SqlDialect {
+    fn begin(self) -> &'static str {
+        match self {
+            SqlDialect::Mysql => "START TRANSACTION;",
+            _ => "BEGIN;",
+        }
+    }
+}
+
+fn table_name(opts: &CsvOutputOpts) -> String {
+    opts.table.clone().unwrap_or_else(|| "data".to_string())
+}
+
+fn create_table(dialect: SqlDialect, table: &str, columns: &[String], kinds: &[SqlKind]) -> String {
+    let defs: Vec<String> = columns
+        .iter()
+        .zip(kinds)
+        .map(|(c, k)| format!("  {} {}", quote_ident(dialect, c), k.sql_type(dialect)))
+        .collect();
+    format!(
+        "CREATE TABLE {} (\n{}\n)",
+        quote_ident(dialect, table),
+        defs.join(",\n")
+    )
+}
+
+fn quote_ident(dialect: SqlDialect, name: &str) -> String {
+    match dialect {
+        SqlDialect::Mysql => format!("`{}`", name.replace('`', "``")),
+        _ => format!("\"{}\"", name.replace('"', "\"\"")),
+    }
+}
+
+fn sql_literal(dialect: SqlDialect, value: &Value, kind: SqlKind) -> String {
+    match (value, kind) {
+        (Value::Null, _) => "NULL".to_string(),
+        (Value::Bool(b), SqlKind::Boolean) => match dialect {
+            SqlDialect::Sqlite => (*b as u8).to_string(),
+            _ => b.to_string().to_uppercase(),
+        },
+        (Value::Number(n), SqlKind::Integer | SqlKind::Real) => n.to_string(),
+        (value, _) => {
+            let text = text_of(value);
+            // MySQL 默认把反斜杠当作转义字符
+            let text = match dialect {
+                SqlDialect::Mysql => text.replace('\\', "\\\\"),
+                _ => text,
+            };
+            format!("'{}'", text.replace('\'', "''"))
+        }
+    }
+}
+
+fn sqlite_value(value: &Value, kind: SqlKind) -> SqliteValue {
+    match (value, kind) {
+        (Value::Null, _) => SqliteValue::Null,
+        (Value::Bool(b), SqlKind::Boolean) => SqliteValue::Integer(*b as i64),
+        (Value::Number(n), SqlKind::Integer) => n
+            .as_i64()
+            .map_or_else(|| SqliteValue::Text(n.to_string()), SqliteValue::Integer),
+        (Value::Number(n), SqlKind::Real) => n
+            .as_f64()
+            .map_or_else(|| SqliteValue::Text(n.to_string()), SqliteValue::Real),
+        (value, _) => SqliteValue::Text(text_of(value)),
+    }
+}
+
+// 嵌套的对象和数组 (比如 --unflatten) 存成 JSON 文本
+pub(crate) fn text_of(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        v => v.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn records() -> Vec<Value> {
+        vec![
+            json!({"Name": "Gianluigi Buffon", "Kit Number": 77, "Rating": 8, "Captain": true}),
+            json!({"Name": "Mattia O'Perin", "Kit Number": null, "Rating": 6.5, "Captain": false}),
+        ]
+    }
+
+    fn render(dialect: SqlDialect) -> String {
+        let opts = CsvOutputOpts {
+            table: Some("players".to_string()),
+            sql_dialect: dialect,
+            batch_size: 1,
+            ..Default::default()
+        };
+        let mut buf = Vec::new();
+        {
+            let mut writer = sql_writer(&mut buf, &opts);
+            for record in records() {
+                writer.write(record).unwrap();
+            }
+            writer.finish().unwrap();
+        }
+        String::from_utf8(buf).unwrap()
+    }
+
+    #[test]
+    fn test_sql_writer_postgres() {
+        let expected = r#"CREATE TABLE "players" (
+  "Name" TEXT,
+  "Kit Number" BIGINT,
+  "Rating" DOUBLE PRECISION,
+  "Captain" BOOLEAN
+);
+
+BEGIN;
+INSERT INTO "players" ("Name", "Kit Number", "Rating", "Captain") VALUES
+  ('Gianluigi Buffon', 77, 8, TRUE);
+COMMIT;
+BEGIN;
+INSERT INTO "players" ("Name", "Kit Number", "Rating", "Captain") VALUES
+  ('Mattia O''Perin', NULL, 6.5, FALSE);
+COMMIT;
+"#;
+        assert_eq!(render(SqlDialect::Postgres), expected);
+        let mysql = render(SqlDialect::Mysql);
+        assert!(mysql.contains("CREATE TABLE `players` (\n  `Name` TEXT,\n  `Kit Number` BIGINT"));
+        assert!(mysql.starts_with("CREATE TABLE") && mysql.contains("START TRANSACTION;"));
+    }
+
+    #[test]
+    fn test_sql_writer_without_rows() {
+        let opts = CsvOutputOpts {
+            table: Some("players".to_string()),
+            ..Default::default()
+        };
+        let mut buf = Vec::new();
+        {
+            let mut writer = sql_writer(&mut buf, &opts);
+            let columns = ["Name", "Rating", "Name"].map(String::from);
+            writer.columns(&columns).unwrap();
+            writer.finish().unwrap();
+        }
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            "CREATE TABLE \"players\" (\n  \"Name\" TEXT,\n  \"Rating\" TEXT\n);\n\n"
+        );
+    }
+
+    #[test]
+    fn test_sqlite_writer() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("players.db");
+        let opts = CsvOutputOpts {
+            table: Some("players".to_string()),
+            ..Default::default()
+        };
+        let mut writer = sqlite_writer(path.to_str().unwrap(), &opts).unwrap();
+        for record in records() {
+            writer.write(record).unwrap();
+        }
+        writer.finish().unwrap();
+
+        let conn = Connection::open(&path).unwrap();
+        let (kit, rating, captain): (i64, f64, i64) = conn
+            .query_row(
+                "SELECT \"Kit Number\", Rating, Captain FROM players WHERE Name = 'Gianluigi Buffon'",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .unwrap();
+        assert_eq!((kit, rating, captain), (77, 8.0, 1));
+        let count: i64 = conn
+            .query_row("SELECT COUNT(*) FROM players", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(count, 2);
+    }
+}
@@ -0,0 +1,584 @@
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    hash::{Hash, Hasher},
+    io::Write,
+};
+
+use anyhow::Result;
+use csv::StringRecord;
+use serde_json::{json, Map, Value};
+
+use super::{
+    csv_convert::CsvRows,
+    csv_infer::infer_cell,
+    csv_table::{render_table, terminal_width},
+    csv_writer::record_writer,
+};
+use crate::{
+    cli::{CsvFilterOpts, CsvOutputOpts, CsvReaderOpts, CsvType, OutputFormat},
+    get_writer,
+};
+
+// 单列精确去重最多保留这么多个不同的值, 超过后切换到 HyperLogLog
+const EXACT_LIMIT: usize = 100_000;
+// HyperLogLog 使用 2^14 个寄存器, 标准误差约 0.8%
+const HLL_BITS: u32 = 14;
+
+#[derive(Default)]
+struct ColumnStats {
+    count: u64,
+    nulls: u64,
+    integers: u64,
+    floats: u64,
+    booleans: u64,
+    median: Median,
+    num_min: f64,
+    num_max: f64,
+    mean: f64,
+    m2: f64,
+    str_min: Option<String>,
+    str_max: Option<String>,
+    distinct: Distinct,
+}
+
+// 精确模式保存所有数值求中位数; --approx 时用 P² 算法, 内存固定
+enum Median {
+    Exact(Vec<f64>),
+    Approx(P2),
+}
+
+// P² 算法 (Jain & Chlamtac 1985), 用 5 个标记估计分位数
+struct P2 {
+    heights: [f64; 5],
+    positions: [f64; 5],
+    desired: [f64; 5],
+    increments: [f64; 5],
+    // 前 5 个值直接保存
+    initial: Vec<f64>,
+}
+
+enum Distinct {
+    Exact(HashMap<String, u64>),
+    Approx { hll: HyperLogLog, heavy: MisraGries },
+}
+
+struct HyperLogLog {
+    registers: Vec<u8>,
+}
+
+// 近似统计高频值: 最多 k 个计数器, 计数是真实值的下界
+struct MisraGries {
+    capacity: usize,
+    counters: HashMap<String, u64>,
+}
+
+pub fn process_csv_stats(
+    input: &str,
+    output: &str,
+    format: Option<OutputFormat>,
+    opts: &CsvReaderOpts,
+    filter: &CsvFilterOpts,
+    top: usize,
+    approx: bool,
+) -> Result<()> {
+    let mut rows = CsvRows::open(input, opts, filter)?;
+    let mut stats: Vec<ColumnStats> = Vec::new();
+    let new_stats = || ColumnStats {
+        median: Median::new(approx),
+        distinct: Distinct::new(approx, top),
+        ..Default::default()
+    };
+    for record in rows.by_ref() {
+        let record = record?;
+        if stats.len() < record.len() {
+            stats.resize_with(record.len(), new_stats);
+        }
+        for (cell, column) in record.iter().zip(stats.iter_mut()) {
+            column.update(cell, top);
+        }
+    }
+    let headers = rows.headers().clone();
+    stats.resize_with(headers.len(), new_stats);
+
+    let columns: Vec<Value> = headers
+        .iter()
+        .zip(stats)
+        .map(|(name, column)| column.finish(name, top))
+        .collect();
+
+    match format {
+        Some(format) => {
+            let mut writer = record_writer(format, output, &CsvOutputOpts::default())?;
+            for column in columns {
+                writer.write(column)?;
+            }
+            writer.finish()
+        }
+        None => {
+            let table = stats_table(&columns);
+            let width = if output == "-" {
+                terminal_width()
+            } else {
+                None
+            };
+            let mut writer = get_writer(output)?;
+            write!(writer, "{}", render_table(&table.0, &table.1, width, false))?;
+            Ok(())
+        }
+    }
+}
+
+impl ColumnStats {
+    fn update(&mut self, cell: &str, top: usize) {
+        if cell.is_empty() {
+            self.nulls += 1;
+            return;
+        }
+        self.count += 1;
+        match infer_cell(cell) {
+            Value::Number(n) => {
+                if n.is_f64() {
+                    self.floats += 1;
+                } else {
+                    self.integers += 1;
+                }
+                let x = n.as_f64().unwrap_or_default();
+                let count = self.integers + self.floats;
+                if count == 1 {
+                    (self.num_min, self.num_max) = (x, x);
+                } else {
+                    self.num_min = self.num_min.min(x);
+                    self.num_max = self.num_max.max(x);
+                }
+                // Welford 算法, 一次遍历得到均值和方差
+                let delta = x - self.mean;
+                self.mean += delta / count as f64;
+                self.m2 += delta * (x - self.mean);
+                self.median.insert(x);
+            }
+            Value::Bool(_) => self.booleans += 1,
+            _ => {}
+        }
+        if self.str_min.as_deref().is_none_or(|m| cell < m) {
+            self.str_min = Some(cell.to_string());
+        }
+        if self.str_max.as_deref().is_none_or(|m| cell > m) {
+            self.str_max = Some(cell.to_string());
+        }
+        self.distinct.insert(cell, top);
+    }
+
+    fn column_type(&self) -> Option<CsvType> {
+        let numbers = self.integers + self.floats;
+        match self.count {
+            0 => None,
+            n if n == self.integers => Some(CsvType::Integer),
+            n if n == numbers => Some(CsvType::Float),
+            n if n == self.booleans => Some(CsvType::Boolean),
+            _ => Some(CsvType::String),
+        }
+    }
+
+    fn finish(self, name: &str, top: usize) -> Value {
+        let ty = self.column_type();
+        let numeric = matches!(ty, Some(CsvType::Integer | CsvType::Float));
+        let (distinct, approx) = self.distinct.count();
+        let mut map = Map::new();
+        map.insert("column".into(), json!(name));
+        map.insert(
+            "type".into(),
+            json!(ty.map_or("empty".to_string(), |t| t.to_string())),
+        );
+        map.insert("count".into(), json!(self.count));
+        map.insert("nulls".into(), json!(self.nulls));
+        map.insert("distinct".into(), json!(distinct));
+        map.insert("distinct_approx".into(), json!(approx));
+        let (median, median_approx) = self.median.finish();
+        if numeric {
+            let n = self.integers + self.floats;
+            let stddev = if n > 1 {
+                (self.m2 / (n - 1) as f64).sqrt()
+            } else {
+                0.0
+            };
+            map.insert("min".into(), number(self.num_min));
+            map.insert("max".into(), number(self.num_max));
+            map.insert("mean".into(), number(self.mean));
+            map.insert("median".into(), number(median));
+            map.insert("median_approx".into(), json!(median_approx));
+            map.insert("stddev".into(), number(stddev));
+        } else {
+            map.insert("min".into(), json!(self.str_min));
+            map.insert("max".into(), json!(self.str_max));
+            map.insert("mean".into(), Value::Null);
+            map.insert("median".into(), Value::Null);
+            map.insert("median_approx".into(), json!(false));
+            map.insert("stddev".into(), Value::Null);
+        }
+        let top: Vec<Value> = self
+            .distinct
+            .top(top)
+            .into_iter()
+            .map(|(value, count)| json!({"value": value, "count": count}))
+            .collect();
+        map.insert("top".into(), Value::Array(top));
+        Value::Object(map)
+    }
+}
+
+// 整数结果不带小数点, 其余保留 4 位小数
+fn number(x: f64) -> Value {
+    if x.fract() == 0.0 && x.abs() < i64::MAX as f64 {
+        json!(x as i64)
+    } else {
+        json!((x * 10_000.0).round() / 10_000.0)
+    }
+}
+
+fn stats_table(columns: &[Value]) -> (StringRecord, Vec<Option<StringRecord>>) {
+    let fields = [
+        "column", "type", "count", "nulls", "distinct", "min", "max", "mean", "median", "stddev",
+        "top",
+    ];
+    let rows = columns
+        .iter()
+        .map(|column| {
+            let record = fields
+                .iter()
+                .map(|field| match (&column[*field], *field) {
+                    (Value::Array(top), _) => top
+                        .iter()
+                        .map(|v| format!("{} ({})", v["value"].as_str().unwrap_or(""), v["count"]))
+                        .collect::<Vec<_>>()
+                        .join(", "),
+                    (Value::Number(n), "distinct") if column["distinct_approx"] == json!(true) => {
+                        format!("~{}", n)
+                    }
+                    (Value::Number(n), "median") if column["median_approx"] == json!(true) => {
+                        format!("~{}", n)
+                    }
+                    (Value::String(s), _) => s.clone(),
+                    (Value::Null, _) => String::new(),
+                    (v, _) => v.to_string(),
+                })
+                .collect();
+            Some(record)
+        })
+        .collect();
+    (StringRecord::from(fields.to_vec()), rows)
+}
+
+impl Median {
+    fn new(approx: bool) -> Self {
+        if approx {
+            Median::Approx(P2::new(0.5))
+        } else {
+            Median::Exact(Vec::new())
+        }
+    }
+
+    fn insert(&mut self, x: f64) {
+        match self {
+            Median::Exact(numbers) => numbers.push(x),
+            Median::Approx(p2) => p2.insert(x),
+        }
+    }
+
+    // 返回 (中位数, 是否为近似值), 没有数值时为 NaN
+    fn finish(self) -> (f64, bool) {
+        match self {
+            Median::Exact(numbers) => (exact_median(numbers), false),
+            Median::Approx(p2) => p2.estimate(),
+        }
+    }
+}
+
+impl Default for Median {
+    fn default() -> Self {
+        Median::Exact(Vec::new())
+    }
+}
+
+fn exact_median(mut numbers: Vec<f64>) -> f64 {
+    numbers.sort_by(f64::total_cmp);
+    let n = numbers.len();
+    match n {
+        0 => f64::NAN,
+        n if n % 2 == 1 => numbers[n / 2],
+        n => (numbers[n / 2 - 1] + numbers[n / 2]) / 2.0,
+    }
+}
+
+impl P2 {
+    fn new(p: f64) -> Self {
+        Self {
+            heights: [0.0; 5],
+            positions: [1.0, 2.0, 3.0, 4.0, 5.0],
+            desired: [1.0, 1.0 + 2.0 * p, 1.0 + 4.0 * p, 3.0 + 2.0 * p, 5.0],
+            increments: [0.0, p / 2.0, p, (1.0 + p) / 2.0, 1.0],
+            initial: Vec::with_capacity(5),
+        }
+    }
+
+    fn insert(&mut self, x: f64) {
+        if self.initial.len() < 5 {
+            self.initial.push(x);
+            if self.initial.len() == 5 {
+                let mut sorted = self.initial.clone();
+                sorted.sort_by(f64::total_cmp);
+                self.heights.copy_from_slice(&sorted);
+            }
+            return;
+        }
+        let q = &mut self.heights;
+        // 找到 x 所在的区间, 超出两端时更新最小值和最大值
+        let k = if x < q[0] {
+            q[0] = x;
+            0
+        } else if x >= q[4] {
+            q[4] = q[4].max(x);
+            3
+        } else {
+            (1..5).find(|&i| x < q[i]).map_or(3, |i| i - 1)
+        };
+        for i in k + 1..5 {
+            self.positions[i] += 1.0;
+        }
+        for i in 0..5 {
+            self.desired[i] += self.increments[i];
+        }
+        for i in 1..4 {
+            let n = &self.positions;
+            let d = self.desired[i] - n[i];
+            if (d >= 1.0 && n[i + 1] - n[i] > 1.0) || (d <= -1.0 && n[i - 1] - n[i] < -1.0) {
+                let d = d.signum();
+                let q = &self.heights;
+                let parabolic = q[i]
+                    + d / (n[i + 1] - n[i - 1])
+                        * ((n[i] - n[i - 1] + d) * (q[i + 1] - q[i]) / (n[i + 1] - n[i])
+                            + (n[i + 1] - n[i] - d) * (q[i] - q[i - 1]) / (n[i] - n[i - 1]));
+                self.heights[i] = if q[i - 1] < parabolic && parabolic < q[i + 1] {
+                    parabolic
+                } else {
+                    let j = if d > 0.0 { i + 1 } else { i - 1 };
+                    q[i] + d * (q[j] - q[i]) / (n[j] - n[i])
+                };
+                self.positions[i] += d;
+            }
+        }
+    }
+
+    // 不超过 5 个值时结果是精确的
+    fn estimate(self) -> (f64, bool) {
+        if self.initial.len() < 5 || self.positions[4] <= 5.0 {
+            (exact_median(self.initial), false)
+        } else {
+            (self.heights[2], true)
+        }
+    }
+}
+
+impl Distinct {
+    fn new(approx: bool, top: usize) -> Self {
+        if approx {
+            Distinct::Approx {
+                hll: HyperLogLog::new(),
+                heavy: MisraGries::new(top),
+            }
+        } else {
+            Distinct::Exact(HashMap::new())
+        }
+    }
+
+    fn insert(&mut self, value: &str, top: usize) {
+        match self {
+            Distinct::Exact(counts) => {
+                *counts.entry(value.to_string()).or_default() += 1;
+                if counts.len() > EXACT_LIMIT {
+                    let counts = std::mem::take(counts);
+                    let mut hll = HyperLogLog::new();
+                    let mut heavy = MisraGries::new(top);
+                    for (value, count) in counts {
+                        hll.insert(&value);
+                        heavy.insert_n(value, count);
+                    }
+                    *self = Distinct::Approx { hll, heavy };
+                }
+            }
+            Distinct::Approx { hll, heavy } => {
+                hll.insert(value);
+                heavy.insert_n(value.to_string(), 1);
+            }
+        }
+    }
+
+    // 返回 (去重数, 是否为近似值)
+    fn count(&self) -> (u64, bool) {
+        match self {
+            Distinct::Exact(counts) => (counts.len() as u64, false),
+            Distinct::Approx { hll, .. } => (hll.estimate(), true),
+        }
+    }
+
+    fn top(&self, n: usize) -> Vec<(String, u64)> {
+        let counts = match self {
+            Distinct::Exact(counts) => counts,
+            Distinct::Approx { heavy, .. } => &heavy.counters,
+        };
+        let mut top: Vec<(String, u64)> = counts.iter().map(|(k, v)| (k.clone(), *v)).collect();
+        // 次数相同时按值排序, 保证输出稳定
+        top.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        top.truncate(n);
+        top
+    }
+}
+
+impl Default for Distinct {
+    fn default() -> Self {
+        Distinct::Exact(HashMap::new())
+    }
+}
+
+impl HyperLogLog {
+    fn new() -> Self {
+        Self {
+            registers: vec![0; 1 << HLL_BITS],
+        }
+    }
+
+    fn insert(&mut self, value: &str) {
+        let mut hasher = DefaultHasher::new();
+        value.hash(&mut hasher);
+        let hash = hasher.finish();
+        let idx = (hash >> (64 - HLL_BITS)) as usize;
+        let rank = ((hash << HLL_BITS) | (1 << (HLL_BITS - 1))).leading_zeros() as u8 + 1;
+        self.registers[idx] = self.registers[idx].max(rank);
+    }
+
+    fn estimate(&self) -> u64 {
+        let m = self.registers.len() as f64;
+        let alpha = 0.7213 / (1.0 + 1.079 / m);
+        let sum: f64 = self.registers.iter().map(|r| 2f64.powi(-(*r as i32))).sum();
+        let estimate = alpha * m * m / sum;
+        let zeros = self.registers.iter().filter(|r| **r == 0).count();
+        // 基数较小时线性计数更准确
+        if estimate <= 2.5 * m && zeros > 0 {
+            (m * (m / zeros as f64).ln()).round() as u64
+        } else {
+            estimate.round() as u64
+        }
+    }
+}
+
+impl MisraGries {
+    fn new(top: usize) -> Self {
+        Self {
+            capacity: (top * 100).max(1000),
+            counters: HashMap::new(),
+        }
+    }
+
+    fn insert_n(&mut self, value: String, n: u64) {
+        if let Some(count) = self.counters.get_mut(&value) {
+            *count += n;
+            return;
+        }
+        if self.counters.len() < self.capacity {
+            self.counters.insert(value, n);
+            return;
+        }
+        // 计数器满了: 所有计数一起减去, 减到 0 的被淘汰
+        let min = self.counters.values().copied().min().unwrap_or(0).min(n);
+        self.counters.values_mut().for_each(|c| *c -= min);
+        self.counters.retain(|_, c| *c > 0);
+        if n > min {
+            self.counters.insert(value, n - min);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn column(cells: &[&str]) -> Value {
+        let mut stats = ColumnStats::default();
+        for cell in cells {
+            stats.update(cell, 2);
+        }
+        stats.finish("c", 2)
+    }
+
+    #[test]
+    fn test_numeric_column_stats() {
+        let value = column(&["1", "2", "", "3", "10", "2"]);
+        assert_eq!(value["type"], "integer");
+        assert_eq!(value["count"], 5);
+        assert_eq!(value["nulls"], 1);
+        assert_eq!(value["distinct"], 4);
+        assert_eq!(value["min"], 1);
+        assert_eq!(value["max"], 10);
+        assert_eq!(value["mean"], 3.6);
+        assert_eq!(value["median"], 2);
+        assert_eq!(value["stddev"], 3.6469);
+        assert_eq!(value["top"][0], json!({"value": "2", "count": 2}));
+    }
+
+    #[test]
+    fn test_string_column_stats() {
+        let value = column(&["Italy", "Poland", "Italy", "1.5"]);
+        assert_eq!(value["type"], "string");
+        assert_eq!(value["min"], "1.5");
+        assert_eq!(value["max"], "Poland");
+        assert_eq!(value["mean"], Value::Null);
+        assert_eq!(value["top"].as_array().unwrap().len(), 2);
+        assert_eq!(column(&["", ""])["type"], "empty");
+        assert_eq!(column(&["1", "1.5"])["type"], "float");
+    }
+
+    #[test]
+    fn test_approx_median() {
+        let mut stats = ColumnStats {
+            median: Median::new(true),
+            distinct: Distinct::new(true, 2),
+            ..Default::default()
+        };
+        for cell in ["3", "1", "2"] {
+            stats.update(cell, 2);
+        }
+        assert_eq!(stats.median.finish(), (2.0, false));
+
+        // 打乱顺序的 1..=10001, 真实中位数是 5001
+        let mut p2 = Median::new(true);
+        for i in 0..10_001u64 {
+            p2.insert((i * 7919 % 10_001 + 1) as f64);
+        }
+        let (median, approx) = p2.finish();
+        assert!(approx);
+        assert!((median - 5001.0).abs() < 100.0, "median {}", median);
+    }
+
+    #[test]
+    fn test_hyperloglog_estimate() {
+        let mut hll = HyperLogLog::new();
+        for i in 0..50_000 {
+            hll.insert(&i.to_string());
+        }
+        let estimate = hll.estimate() as f64;
+        assert!((estimate - 50_000.0).abs() / 50_000.0 < 0.03);
+    }
+
+    #[test]
+    fn test_misra_gries_keeps_heavy_hitters() {
+        let mut heavy = MisraGries {
+            capacity: 3,
+            counters: HashMap::new(),
+        };
+        for i in 0..100 {
+            heavy.insert_n("hot".into(), 1);
+            heavy.insert_n(i.to_string(), 1);
+        }
+        assert!(heavy.counters.contains_key("hot"));
+        assert!(heavy.counters.len() <= 3);
+    }
+}
@@ -0,0 +1,257 @@
+use std::{collections::VecDeque, io::Write};
+
+use anyhow::Result;
+use csv::StringRecord;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+use super::{csv_convert::CsvRows, csv_infer::infer_cell};
+use crate::{
+    cli::{CsvFilterOpts, CsvReaderOpts, CsvTableOpts},
+    get_writer,
+};
+
+// 列宽最少保留的字符数, 再窄就没法看了
+const MIN_WIDTH: usize = 3;
+
+pub fn process_csv_show(
+    input: &str,
+    opts: &CsvReaderOpts,
+    filter: &CsvFilterOpts,
+    table: &CsvTableOpts,
+) -> Result<()> {
+    let mut rows = CsvRows::open(input, opts, filter)?;
+    let mut head = Vec::new();
+    let mut tail = VecDeque::new();
+    let mut total = 0;
+    for record in rows.by_ref() {
+        let record = record?;
+        total += 1;
+        match (table.head, table.tail) {
+            (Some(n), _) if head.len() < n => head.push(record),
+            (_, Some(n)) => {
+                if tail.len() == n {
+                    tail.pop_front();
+                }
+                if n > 0 {
+                    tail.push_back(record);
+                }
+            }
+            // 只有 --head 时剩下的行只计数
+            (Some(_), None) => {}
+            (None, None) => head.push(record),
+        }
+    }
+
+    // 中间被省略的行用 None 占位
+    let shown = head.len() + tail.len();
+    let mut table_rows: Vec<Option<StringRecord>> = head.into_iter().map(Some).collect();
+    if shown < total && table.tail.is_some() {
+        table_rows.push(None);
+    }
+    table_rows.extend(tail.into_iter().map(Some));
+
+    let width = table.width.or_else(terminal_width);
+    let mut writer = get_writer("-")?;
+    write!(
+        writer,
+        "{}",
+        render_table(rows.headers(), &table_rows, width, table.wrap)
+    )?;
+    if shown < total {
+        writeln!(writer, "({} of {} rows)", shown, total)?;
+    } else {
+        writeln!(writer, "({} rows)", total)?;
+    }
+    Ok(())
+}
+
+// stdout 不是终端 (比如被重定向) 时返回 None, 不限制宽度
+pub(crate) fn terminal_width() -> Option<usize> {
+    terminal_size::terminal_size().map(|(terminal_size::Width(w), _)| w as usize)
+}
+
+pub(crate) fn render_table(
+    headers: &StringRecord,
+    rows: &[Option<StringRecord>],
+    width: Option<usize>,
+    wrap: bool,
+) -> String {
+    let mut widths: Vec<usize> = headers.iter().map(|h| h.width().max(1)).collect();
+    for record in rows.iter().flatten() {
+        for (i, cell) in record.iter().enumerate().take(widths.len()) {
+            widths[i] = widths[i].max(cell.width());
+        }
+    }
+    if let Some(width) = width {
+        fit_widths(&mut widths, width);
+    }
+
+    let mut out = String::new();
+    out.push_str(&border(&widths, '┌', '┬', '┐'));
+    out.push_str(&render_row(headers, &widths, wrap, false));
+    out.push_str(&border(&widths, '├', '┼', '┤'));
+    for row in rows {
+        match row {
+            Some(record) => out.push_str(&render_row(record, &widths, wrap, true)),
+            None => {
+                let ellipsis = widths.iter().map(|_| "…").collect::<StringRecord>();
+                out.push_str(&render_row(&ellipsis, &widths, false, false));
+            }
+        }
+    }
+    out.push_str(&border(&widths, '└', '┴', '┘'));
+    out
+}
+
+// 每列占用 `│ ` + 内容 + ` `, 再加上最右边的 `│`; 超出时反复收窄最宽的列
+fn fit_widths(widths: &mut [usize], total: usize) {
+    let budget = total.saturating_sub(3 * widths.len() + 1);
+    while widths.iter().sum::<usize>() > budget {
+        let Some((idx, w)) = widths.iter().enumerate().max_by_key(|(_, w)| **w) else {
+            return;
+        };
+        if *w <= MIN_WIDTH {
+            return;
+        }
+        widths[idx] -= 1;
+    }
+}
+
+fn border(widths: &[usize], left: char, mid: char, right: char) -> String {
+    let segments: Vec<String> = widths.iter().map(|w| "─".repeat(w + 2)).collect();
+    format!("{}{}{}\n", left, segments.join(&mid.to_string()), right)
+}
+
+fn render_row(record: &StringRecord, widths: &[usize], wrap: bool, align: bool) -> String {
+    let cells: Vec<Vec<String>> = widths
+        .iter()
+        .enumerate()
+        .map(|(i, w)| {
+            let cell = record.get(i).unwrap_or("");
+            if wrap {
+                wrap_cell(cell, *w)
+            } else {
+                vec![truncate_cell(cell, *w)]
+            }
+        })
+        .collect();
+    let height = cells.iter().map(Vec::len).max().unwrap_or(1);
+
+    let mut out = String::new();
+    for line in 0..height {
+        for (i, w) in widths.iter().enumerate() {
+            let text = cells[i].get(line).map_or("", String::as_str);
+            let pad = " ".repeat(w.saturating_sub(text.width()));
+            // 数字右对齐, 方便比较大小
+            let numeric = align && infer_cell(record.get(i).unwrap_or("")).is_number();
+            if numeric {
+                out.push_str(&format!("│ {}{} ", pad, text));
+            } else {
+                out.push_str(&format!("│ {}{} ", text, pad));
+            }
+        }
+        out.push_str("│\n");
+    }
+    out
+}
+
+fn truncate_cell(cell: &str, width: usize) -> String {
+    // 换行符会破坏表格, 统一替换成空格
+    let cell = cell.replace(['\r', '\n'], " ");
+    if cell.width() <= width {
+        return cell;
+    }
+    let mut out = String::new();
+    let mut used = 0;
+    for c in cell.chars() {
+        let w = c.width().unwrap_or(0);
+        if used + w + 1 > width {
+            break;
+        }
+        out.push(c);
+        used += w;
+    }
+    out.push('…');
+    out
+}
+
+// 优先在空白处断行, 单个词超过列宽时再硬切
+fn wrap_cell(cell: &str, width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    for word in cell.split_whitespace() {
+        let sep = if line.is_empty() { 0 } else { 1 };
+        if line.width() + sep + word.width() <= width {
+            if sep == 1 {
+                line.push(' ');
+            }
+            line.push_str(word);
+            continue;
+        }
+        if !line.is_empty() {
+            lines.push(std::mem::take(&mut line));
+        }
+        for c in word.chars() {
+            if line.width() + c.width().unwrap_or(0) > width {
+                lines.push(std::mem::take(&mut line));
+            }
+            line.push(c);
+        }
+    }
+    if !line.is_empty() || lines.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers() -> StringRecord {
+        StringRecord::from(vec!["Name", "Kit Number"])
+    }
+
+    #[test]
+    fn test_render_table_aligns_numbers() {
+        let rows = vec![
+            Some(StringRecord::from(vec!["Buffon", "77"])),
+            None,
+            Some(StringRecord::from(vec!["Perin", "1"])),
+        ];
+        let table = render_table(&headers(), &rows, None, false);
+        let expected = "\
+┌────────┬────────────┐
+│ Name   │ Kit Number │
+├────────┼────────────┤
+│ Buffon │         77 │
+│ …      │ …          │
+│ Perin  │          1 │
+└────────┴────────────┘
+";
+        assert_eq!(table, expected);
+    }
+
+    #[test]
+    fn test_render_table_fits_width() {
+        let rows = vec![Some(StringRecord::from(vec!["Wojciech Szczesny", "1"]))];
+        let table = render_table(&headers(), &rows, Some(24), false);
+        assert!(table.lines().all(|l| l.width() <= 24));
+        assert!(table.contains("Wojciech…"));
+
+        let table = render_table(&headers(), &rows, Some(24), true);
+        assert!(table.lines().any(|l| l.starts_with("│ Wojciech ")));
+        assert!(table.lines().any(|l| l.starts_with("│ Szczesny ")));
+    }
+
+    #[test]
+    fn test_wrap_cell() {
+        assert_eq!(
+            wrap_cell("Apr 18, 1990 (29)", 8),
+            vec!["Apr 18,", "1990", "(29)"]
+        );
+        assert_eq!(wrap_cell("abcdefgh", 3), vec!["abc", "def", "gh"]);
+        assert_eq!(wrap_cell("", 3), vec![""]);
+        assert_eq!(truncate_cell("中文字段", 5), "中文…");
+    }
+}
@@ -0,0 +1,231 @@
+use std::collections::BTreeMap;
+
+use anyhow::{anyhow, Result};
+use csv::StringRecord;
+use serde_json::{Map, Value};
+
+// 表头按路径组成的树, 叶子保存原始的表头名
+#[derive(Debug)]
+pub(crate) enum Unflatten {
+    Leaf(String),
+    Object(Vec<(String, Unflatten)>),
+    Array(BTreeMap<usize, Unflatten>),
+}
+
+// 数组下标的上限, 中间缺失的下标会补 null, 过大的下标会占用大量内存
+const MAX_INDEX: usize = 10_000;
+
+const NESTED_VALUE: &str = "a value cannot also contain nested fields";
+
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    Key(String),
+    Index(usize),
+}
+
+impl Unflatten {
+    // address.city, tags[0], players[1].name => 嵌套的对象和数组, 路径冲突时报错
+    pub(crate) fn new(headers: &StringRecord) -> Result<Self> {
+        let mut root = Unflatten::Object(Vec::new());
+        for header in headers {
+            let path = parse_path(header)?;
+            root.insert(&path, header)?;
+        }
+        Ok(root)
+    }
+
+    // 输入是 CellTyper::row 得到的扁平对象
+    pub(crate) fn apply(&self, value: Value) -> Value {
+        match value {
+            Value::Object(mut flat) => self.build(&mut flat),
+            v => v,
+        }
+    }
+
+    fn build(&self, flat: &mut Map<String, Value>) -> Value {
+        match self {
+            Unflatten::Leaf(header) => flat.remove(header).unwrap_or(Value::Null),
+            Unflatten::Object(children) => Value::Object(
+                children
+                    .iter()
+                    .map(|(key, node)| (key.clone(), node.build(flat)))
+                    .collect(),
+            ),
+            // 中间缺失的下标用 null 补齐
+            Unflatten::Array(items) => {
+                let len = items
+                    .keys()
+                    .next_back()
+                    .map_or(Some(0), |i| i.checked_add(1))
+                    .expect("indices are capped in parse_path");
+                let mut arr = vec![Value::Null; len];
+                for (i, node) in items {
+                    arr[*i] = node.build(flat);
+                }
+                Value::Array(arr)
+            }
+        }
+    }
+
+    fn insert(&mut self, path: &[Segment], header: &str) -> Result<()> {
+        let (segment, rest) = path.split_first().expect("path is never empty");
+        let existing = self.first_leaf().to_string();
+        let (child, created) = match (self, segment) {
+            (Unflatten::Object(children), Segment::Key(key)) => {
+                match children.iter().position(|(k, _)| k == key) {
+                    Some(i) => (&mut children[i].1, false),
+                    None => {
+                        children.push((key.clone(), Unflatten::empty(rest, header)));
+                        (&mut children.last_mut().expect("just pushed").1, true)
+                    }
+                }
+            }
+            (Unflatten::Array(items), Segment::Index(i)) => {
+                let created = !items.contains_key(i);
+                let child = items
+                    .entry(*i)
+                    .or_insert_with(|| Unflatten::empty(rest, header));
+                (child, created)
+            }
+            (Unflatten::Leaf(_), _) => return Err(conflict(&existing, header, NESTED_VALUE)),
+            _ => {
+                return Err(conflict(
+                    &existing,
+                    header,
+                    "a path cannot be both an object and an array",
+                ))
+            }
+        };
+        match (child, rest.is_empty()) {
+            (_, true) if created => Ok(()),
+            (Unflatten::Leaf(h), true) => Err(conflict(h, header, "the same path is set twice")),
+            (child, true) => Err(conflict(child.first_leaf(), header, NESTED_VALUE)),
+            (child, false) => child.insert(rest, header),
+        }
+    }
+
+    // 根据剩余路径的第一段决定新节点的类型
+    fn empty(rest: &[Segment], header: &str) -> Self {
+        match rest.first() {
+            None => Unflatten::Leaf(header.to_string()),
+            Some(Segment::Key(_)) => Unflatten::Object(Vec::new()),
+            Some(Segment::Index(_)) => Unflatten::Array(BTreeMap::new()),
+        }
+    }
+
+    fn first_leaf(&self) -> &str {
+        match self {
+            Unflatten::Leaf(header) => header,
+            Unflatten::Object(children) => children.first().map_or("", |(_, n)| n.first_leaf()),
+            Unflatten::Array(items) => items.values().next().map_or("", |n| n.first_leaf()),
+        }
+    }
+}
+
+fn conflict(existing: &str, header: &str, reason: &str) -> anyhow::Error {
+    anyhow!(
+        "Conflicting headers '{}' and '{}': {}",
+        existing,
+        header,
+        reason
+    )
+}
+
+fn parse_path(header: &str) -> Result<Vec<Segment>> {
+    let invalid = || anyhow!("Invalid path in header '{}'", header);
+    let mut path = Vec::new();
+    for part in header.split('.') {
+        let (key, mut indices) = match part.find('[') {
+            Some(pos) => (&part[..pos], &part[pos..]),
+            None => (part, ""),
+        };
+        // 每一段都要以字段名开头, [0] 和 a.[0] 这样的写法不合法
+        if key.is_empty() {
+            return Err(invalid());
+        }
+        path.push(Segment::Key(key.to_string()));
+        while !indices.is_empty() {
+            let end = indices.find(']').ok_or_else(invalid)?;
+            let index: usize = indices[1..end].parse().map_err(|_| invalid())?;
+            if index > MAX_INDEX {
+                return Err(anyhow!(
+                    "Index too large in header '{}': {} (max {})",
+                    header,
+                    index,
+                    MAX_INDEX
+                ));
+            }
+            path.push(Segment::Index(index));
+            indices = &indices[end + 1..];
+            if !indices.is_empty() && !indices.starts_with('[') {
+                return Err(invalid());
+            }
+        }
+    }
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn unflatten(headers: &[&str]) -> Result<Unflatten> {
+        Unflatten::new(&StringRecord::from(headers.to_vec()))
+    }
+
+    #[test]
+    fn test_unflatten_nested() {
+        let tree =
+            unflatten(&["name", "address.city", "address.zip", "tags[1]", "tags[0]"]).unwrap();
+        let flat = json!({
+            "name": "Buffon",
+            "address.city": "Turin",
+            "address.zip": 10121,
+            "tags[1]": "captain",
+            "tags[0]": "gk",
+        });
+        assert_eq!(
+            serde_json::to_string(&tree.apply(flat)).unwrap(),
+            r#"{"name":"Buffon","address":{"city":"Turin","zip":10121},"tags":["gk","captain"]}"#
+        );
+
+        let tree = unflatten(&["teams[0].name", "teams[2].name"]).unwrap();
+        let flat = json!({"teams[0].name": "Parma", "teams[2].name": "PSG"});
+        assert_eq!(
+            tree.apply(flat),
+            json!({"teams": [{"name": "Parma"}, null, {"name": "PSG"}]})
+        );
+    }
+
+    #[test]
+    fn test_unflatten_conflicts() {
+        let err = unflatten(&["address", "address.city"]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Conflicting headers 'address' and 'address.city': a value cannot also contain nested fields"
+        );
+        let err = unflatten(&["address.city", "address"]).unwrap_err();
+        assert!(err.to_string().contains("'address.city' and 'address'"));
+        let err = unflatten(&["tags[0]", "tags.first"]).unwrap_err();
+        assert!(err.to_string().contains("both an object and an array"));
+        assert!(unflatten(&["a.b", "a.b"]).is_err());
+        assert!(unflatten(&["a..b"]).is_err());
+        assert!(unflatten(&["a[x]"]).is_err());
+        assert!(unflatten(&["[0]"]).is_err());
+    }
+
+    #[test]
+    fn test_unflatten_index_too_large() {
+        let err = unflatten(&["tags[1000000000000]"]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Index too large in header 'tags[1000000000000]': 1000000000000 (max 10000)"
+        );
+        let err = unflatten(&["tags[18446744073709551615]"]).unwrap_err();
+        assert!(err.to_string().starts_with("Index too large"));
+        // 超出 usize 的下标按格式错误处理
+        assert!(unflatten(&["tags[18446744073709551616]"]).is_err());
+        assert!(unflatten(&["tags[10000]"]).is_ok());
+    }
+}
@@ -0,0 +1,377 @@
+use std::{collections::HashMap, fs, io::Write};
+
+use anyhow::{anyhow, Result};
+use chrono::{NaiveDate, NaiveDateTime};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::{csv_convert::CsvRows, csv_infer::convert_cell, csv_writer::record_writer};
+use crate::{
+    cli::{CsvFilterOpts, CsvReaderOpts, CsvType, OutputFormat},
+    get_writer,
+};
+
+// schema.yaml 的结构, 例如 assets/juventus.schema.yaml
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Schema {
+    // 为 true 时不允许出现 schema 中没有声明的列
+    #[serde(default)]
+    strict: bool,
+    columns: Vec<ColumnSchema>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ColumnSchema {
+    name: String,
+    #[serde(default, rename = "type")]
+    ty: Option<ColumnType>,
+    // 列必须存在
+    #[serde(default = "default_true")]
+    required: bool,
+    // 单元格可以为空
+    #[serde(default = "default_true")]
+    nullable: bool,
+    #[serde(default)]
+    unique: bool,
+    pattern: Option<String>,
+    #[serde(rename = "enum")]
+    values: Option<Vec<String>>,
+    min: Option<f64>,
+    max: Option<f64>,
+    // 日期格式, 语法同 chrono, 默认 %Y-%m-%d
+    format: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum ColumnType {
+    String,
+    Integer,
+    Float,
+    Boolean,
+    Date,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub(crate) struct Violation {
+    // 表头相关的问题在没有表头行的输入中没有行号
+    row: Option<u64>,
+    column: Option<usize>,
+    name: Option<String>,
+    message: String,
+}
+
+// 编译好的列规则, 对应表头中的某一列
+struct ColumnRule<'a> {
+    index: usize,
+    schema: &'a ColumnSchema,
+    pattern: Option<Regex>,
+    seen: HashMap<String, u64>,
+}
+
+pub fn process_csv_validate(
+    input: &str,
+    schema: &str,
+    output: &str,
+    format: Option<OutputFormat>,
+    opts: &CsvReaderOpts,
+) -> Result<()> {
+    let schema: Schema = serde_yaml::from_str(&fs::read_to_string(schema)?)
+        .map_err(|e| anyhow!("Invalid schema {}: {}", schema, e))?;
+    let rows = CsvRows::open(input, opts, &CsvFilterOpts::default())?;
+    let violations = validate(&schema, rows)?;
+
+    match format {
+        Some(format) => {
+            let mut writer = record_writer(format, output, &Default::default())?;
+            for v in &violations {
+                writer.write(serde_json::to_value(v)?)?;
+            }
+            writer.finish()?;
+        }
+        None => {
+            let mut writer = get_writer(output)?;
+            for v in &violations {
+                let row = v.row.map(|row| format!("row {}", row));
+                let column = match (v.column, &v.name) {
+                    (Some(col), Some(name)) => Some(format!("column {} ({})", col, name)),
+                    _ => None,
+                };
+                let location: Vec<String> = row.into_iter().chain(column).collect();
+                match location.is_empty() {
+                    true => writeln!(writer, "{}", v.message)?,
+                    false => writeln!(writer, "{}: {}", location.join(", "), v.message)?,
+                }
+            }
+        }
+    }
+    // 有错误时返回非零退出码, 方便在 CI 中使用
+    if violations.is_empty() {
+        Ok(())
+    } else {
+        Err(anyhow!(
+            "{} violation(s) found in {}",
+            violations.len(),
+            input
+        ))
+    }
+}
+
+pub(crate) fn validate(schema: &Schema, mut rows: CsvRows) -> Result<Vec<Violation>> {
+    let headers = rows.headers().clone();
+    let header_row = rows.header_line();
+    let mut violations = Vec::new();
+    let mut rules = Vec::new();
+    for column in &schema.columns {
+        let pattern = match &column.pattern {
+            Some(p) => Some(
+                Regex::new(p).map_err(|e| anyhow!("Invalid pattern for {}: {}", column.name, e))?,
+            ),
+            None => None,
+        };
+        match headers.iter().position(|h| h == column.name) {
+            Some(index) => rules.push(ColumnRule {
+                index,
+                schema: column,
+                pattern,
+                seen: HashMap::new(),
+            }),
+            None if column.required => violations.push(Violation {
+                row: header_row,
+                column: None,
+                name: Some(column.name.clone()),
+                message: format!("missing required column '{}'", column.name),
+            }),
+            None => {}
+        }
+    }
+    if schema.strict {
+        for (i, h) in headers.iter().enumerate() {
+            if !schema.columns.iter().any(|c| c.name == h) {
+                violations.push(Violation {
+                    row: header_row,
+                    column: Some(i + 1),
+                    name: Some(h.to_string()),
+                    message: "column is not declared in the schema".to_string(),
+                });
+            }
+        }
+    }
+
+    while let Some(record) = rows.next() {
+        let record = record?;
+        let row = rows.line();
+        for rule in rules.iter_mut() {
+            let cell = record.get(rule.index).unwrap_or("");
+            if let Err(message) = rule.check(cell, row) {
+                violations.push(Violation {
+                    row: Some(row),
+                    column: Some(rule.index + 1),
+                    name: Some(rule.schema.name.clone()),
+                    message,
+                });
+            }
+        }
+    }
+    Ok(violations)
+}
+
+impl ColumnRule<'_> {
+    // 每个单元格只报告第一个不满足的规则
+    fn check(&mut self, cell: &str, row: u64) -> Result<(), String> {
+        let schema = self.schema;
+        if cell.is_empty() {
+            return match schema.nullable {
+                true => Ok(()),
+                false => Err("value is required".to_string()),
+            };
+        }
+        // 先于其他规则检查, 不合法的值重复出现时也要报告
+        if schema.unique {
+            if let Some(first) = self.seen.get(cell) {
+                return Err(format!(
+                    "duplicate value '{}' (first seen at row {})",
+                    cell, first
+                ));
+            }
+            self.seen.insert(cell.to_string(), row);
+        }
+        let value = match schema.ty {
+            Some(ColumnType::Date) => check_date(cell, schema.format.as_deref())?,
+            Some(ty) => convert_cell(cell, ty.into()).map_err(|e| e.to_string())?,
+            None => Value::String(cell.to_string()),
+        };
+        if let Some(pattern) = &self.pattern {
+            if !pattern.is_match(cell) {
+                return Err(format!("'{}' does not match {}", cell, pattern));
+            }
+        }
+        if let Some(values) = &schema.values {
+            if !values.iter().any(|v| v == cell) {
+                return Err(format!("'{}' is not one of {:?}", cell, values));
+            }
+        }
+        if schema.min.is_some() || schema.max.is_some() {
+            let n = value
+                .as_f64()
+                .or_else(|| cell.trim().parse().ok())
+                .ok_or_else(|| format!("'{}' is not a number", cell))?;
+            if schema.min.is_some_and(|min| n < min) || schema.max.is_some_and(|max| n > max) {
+                return Err(format!(
+                    "{} is out of range [{}, {}]",
+                    cell,
+                    schema.min.map_or("-inf".to_string(), |v| v.to_string()),
+                    schema.max.map_or("inf".to_string(), |v| v.to_string()),
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+fn check_date(cell: &str, format: Option<&str>) -> Result<Value, String> {
+    let format = format.unwrap_or("%Y-%m-%d");
+    if NaiveDate::parse_from_str(cell, format).is_ok()
+        || NaiveDateTime::parse_from_str(cell, format).is_ok()
+    {
+        Ok(Value::String(cell.to_string()))
+    } else {
+        Err(format!("'{}' is not a date in format {}", cell, format))
+    }
+}
+
+fn default_true() -> bool {
+    true
+}
+
+impl From<ColumnType> for CsvType {
+    fn from(ty: ColumnType) -> Self {
+        match ty {
+            ColumnType::Integer => CsvType::Integer,
+            ColumnType::Float => CsvType::Float,
+            ColumnType::Boolean => CsvType::Boolean,
+            ColumnType::String | ColumnType::Date => CsvType::String,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{super::test_utils::temp_file, *};
+
+    fn check(schema: &str, csv: &str) -> Vec<String> {
+        check_with(schema, csv, &CsvReaderOpts::default())
+    }
+
+    fn check_with(schema: &str, csv: &str, opts: &CsvReaderOpts) -> Vec<String> {
+        let schema: Schema = serde_yaml::from_str(schema).unwrap();
+        let file = temp_file(csv);
+        let rows = CsvRows::open(
+            file.path().to_str().unwrap(),
+            opts,
+            &CsvFilterOpts::default(),
+        )
+        .unwrap();
+        validate(&schema, rows)
+            .unwrap()
+            .into_iter()
+            .map(|v| {
+                let row = v.row.map_or("-".to_string(), |r| r.to_string());
+                format!("{}:{}:{}", row, v.column.unwrap_or(0), v.message)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_validate_juventus_schema() {
+        let schema = fs::read_to_string("assets/juventus.schema.yaml").unwrap();
+        let csv = fs::read_to_string("assets/juventus.csv").unwrap();
+        assert!(check(&schema, &csv).is_empty());
+    }
+
+    #[test]
+    fn test_validate_rules() {
+        let schema = r#"
+strict: true
+columns:
+  - name: Name
+    nullable: false
+    unique: true
+    pattern: "^[A-Z]"
+  - name: Kit Number
+    type: integer
+    min: 1
+    max: 99
+  - name: Position
+    enum: [Goalkeeper, Centre-Back]
+  - name: Joined
+    type: date
+    format: "%Y-%m-%d"
+  - name: Captain
+    type: boolean
+    required: false
+"#;
+        let csv = "Name,Kit Number,Position,Joined,Extra\n\
+            Gianluigi Buffon,77,Goalkeeper,2019-07-04,\n\
+            ,100,Striker,04/07/2019,\n\
+            Gianluigi Buffon,x1,Centre-Back,,\n\
+            leonardo Bonucci,19,Centre-Back,2018-08-02,\n\
+            leonardo Bonucci,19,Centre-Back,2018-08-02,\n";
+        assert_eq!(
+            check(schema, csv),
+            vec![
+                "1:5:column is not declared in the schema",
+                "3:1:value is required",
+                "3:2:100 is out of range [1, 99]",
+                "3:3:'Striker' is not one of [\"Goalkeeper\", \"Centre-Back\"]",
+                "3:4:'04/07/2019' is not a date in format %Y-%m-%d",
+                "4:1:duplicate value 'Gianluigi Buffon' (first seen at row 2)",
+                "4:2:invalid integer: x1",
+                "5:1:'leonardo Bonucci' does not match ^[A-Z]",
+                "6:1:duplicate value 'leonardo Bonucci' (first seen at row 5)",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_validate_missing_column() {
+        let schema = "columns:\n  - name: Name\n  - name: Goals\n";
+        assert_eq!(
+            check(schema, "Name\nBuffon\n"),
+            vec!["1:0:missing required column 'Goals'"]
+        );
+        assert!(serde_yaml::from_str::<Schema>("columns:\n  - name: a\n    typo: 1\n").is_err());
+    }
+
+    #[test]
+    fn test_validate_row_numbers() {
+        let schema = "columns:\n  - name: col2\n    type: integer\n  - name: col3\n";
+        let headerless = CsvReaderOpts {
+            header: false,
+            ..Default::default()
+        };
+        assert_eq!(
+            check_with(schema, "a,1\nb,x\n", &headerless),
+            vec![
+                "-:0:missing required column 'col3'",
+                "2:2:invalid integer: x"
+            ]
+        );
+
+        let spec = temp_file("skip: 2\ncolumns:\n  - {name: col2, start: 1, width: 3}\n");
+        let fixed = CsvReaderOpts {
+            fixed_width: Some(spec.path().to_str().unwrap().to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            check_with(schema, "REPORT\n======\n12\nabc\n", &fixed),
+            vec![
+                "-:0:missing required column 'col3'",
+                "4:1:invalid integer: abc"
+            ]
+        );
+    }
+}
@@ -0,0 +1,662 @@
+use std::io::{BufWriter, Write};
+
+use anyhow::Result;
+use serde::Serialize;
+use serde_json::Value;
+
+use super::{
+    csv_arrow::columnar_writer,
+    csv_infer::drop_nulls,
+    csv_sql::{sql_writer, sqlite_writer},
+};
+use crate::{
+    cli::{CsvOutputOpts, OutputFormat},
+    get_writer,
+};
+
+// 逐条写出记录, 内存占用与输入大小无关
+pub(crate) trait RecordWriter {
+    fn write(&mut self, record: Value) -> Result<()>;
+    fn finish(&mut self) -> Result<()>;
+
+    // 提前告知列名, 表格类的输出在没有任何记录时也能按表头建表
+    fn columns(&mut self, _columns: &[String]) -> Result<()> {
+        Ok(())
+    }
+}
+
+// 表头取自 columns(), 没有调用时 (比如 --unflatten) 取自第一条记录; 嵌套的值写成 JSON 文本
+struct CsvWriter<W: Write> {
+    writer: csv::Writer<W>,
+    headers: Option<Vec<String>>,
+}
+
+struct JsonWriter<W: Write> {
+    writer: W,
+    count: usize,
+}
+
+struct NdjsonWriter<W: Write> {
+    writer: W,
+}
+
+struct YamlWriter<W: Write> {
+    writer: W,
+    count: usize,
+}
+
+struct TomlWriter<W: Write> {
+    writer: W,
+}
+
+// 表头取自 columns() 或第一条记录, 推迟到第一条记录或 finish 时写出, 数字列的对齐需要看到值
+struct MarkdownWriter<W: Write> {
+    writer: W,
+    headers: Option<Vec<String>>,
+    started: bool,
+}
+
+struct HtmlWriter<W: Write> {
+    writer: W,
+    headers: Option<Vec<String>>,
+    standalone: bool,
+    started: bool,
+}
+
+struct XmlWriter<W: Write> {
+    writer: W,
+    root: String,
+    row: String,
+    field: Option<String>,
+    started: bool,
+}
+
+pub(crate) fn record_writer(
+    format: OutputFormat,
+    output: &str,
+    opts: &CsvOutputOpts,
+) -> Result<Box<dyn RecordWriter>> {
+    // sqlite 直接写数据库文件, parquet/arrow 在 finish 时才打开输出
+    match format {
+        OutputFormat::Sqlite => return Ok(Box::new(sqlite_writer(output, opts)?)),
+        OutputFormat::Parquet | OutputFormat::Arrow => {
+            return Ok(Box::new(columnar_writer(format, output, opts)?))
+        }
+        _ => {}
+    }
+    let writer = BufWriter::new(get_writer(output)?);
+    let writer: Box<dyn RecordWriter> = match format {
+        OutputFormat::Csv => Box::new(CsvWriter {
+            writer: csv::Writer::from_writer(writer),
+            headers: None,
+        }),
+        OutputFormat::Json => Box::new(JsonWriter { writer, count: 0 }),
+        OutputFormat::Ndjson => Box::new(NdjsonWriter { writer }),
+        OutputFormat::Yaml => Box::new(YamlWriter { writer, count: 0 }),
+        OutputFormat::Toml => Box::new(TomlWriter { writer }),
+        OutputFormat::Markdown => Box::new(MarkdownWriter {
+            writer,
+            headers: None,
+            started: false,
+        }),
+        OutputFormat::Html => Box::new(HtmlWriter {
+            writer,
+            headers: None,
+            standalone: opts.standalone,
+            started: false,
+        }),
+        OutputFormat::Xml => Box::new(XmlWriter {
+            writer,
+            root: xml_name(&opts.xml_root),
+            row: xml_name(&opts.xml_row),
+            field: opts.xml_field.as_deref().map(xml_name),
+            started: false,
+        }),
+        OutputFormat::Sql => Box::new(sql_writer(writer, opts)),
+        OutputFormat::Sqlite | OutputFormat::Parquet | OutputFormat::Arrow => {
+            unreachable!("handled above")
+        }
+    };
+    Ok(writer)
+}
+
+impl<W: Write> RecordWriter for CsvWriter<W> {
+    fn write(&mut self, record: Value) -> Result<()> {
+        if self.headers.is_none() {
+            let headers = record_keys(&record);
+            self.writer.write_record(&headers)?;
+            self.headers = Some(headers);
+        }
+        let cells = self.headers.iter().flatten().map(|h| cell_text(&record[h]));
+        self.writer.write_record(cells)?;
+        Ok(())
+    }
+
+    // 立即写出表头, 没有记录时输出也保留表头
+    fn columns(&mut self, columns: &[String]) -> Result<()> {
+        if self.headers.is_none() {
+            let headers = unique_columns(columns);
+            self.writer.write_record(&headers)?;
+            self.headers = Some(headers);
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+impl<W: Write> RecordWriter for JsonWriter<W> {
+    // 与 serde_json::to_string_pretty(&Vec<Value>) 的输出保持一致
+    fn write(&mut self, record: Value) -> Result<()> {
+        let sep = if self.count == 0 { "[\n" } else { ",\n" };
+        self.writer.write_all(sep.as_bytes())?;
+        let pretty = serde_json::to_string_pretty(&record)?;
+        for (i, line) in pretty.lines().enumerate() {
+            if i > 0 {
+                self.writer.write_all(b"\n")?;
+            }
+            write!(self.writer, "  {}", line)?;
+        }
+        self.count += 1;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        let end = if self.count == 0 { "[]" } else { "\n]" };
+        self.writer.write_all(end.as_bytes())?;
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+impl<W: Write> RecordWriter for NdjsonWriter<W> {
+    fn write(&mut self, record: Value) -> Result<()> {
+        serde_json::to_writer(&mut self.writer, &record)?;
+        self.writer.write_all(b"\n")?;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+impl<W: Write> RecordWriter for YamlWriter<W> {
+    // 每条记录单独序列化成只有一个元素的序列, 拼接起来仍然是合法的 YAML 序列
+    fn write(&mut self, record: Value) -> Result<()> {
+        self.writer
+            .write_all(serde_yaml::to_string(&[record])?.as_bytes())?;
+        self.count += 1;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        if self.count == 0 {
+            self.writer.write_all(b"[]\n")?;
+        }
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+impl<W: Write> RecordWriter for TomlWriter<W> {
+    // 同样的道理, 每条记录都是一个 `[[a]]` table
+    fn write(&mut self, mut record: Value) -> Result<()> {
+        #[derive(Serialize)]
+        struct A {
+            a: [Value; 1],
+        }
+        drop_nulls(&mut record);
+        let data = A { a: [record] };
+        writeln!(self.writer, "{}", toml::to_string_pretty(&data)?)?;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+impl<W: Write> RecordWriter for MarkdownWriter<W> {
+    fn write(&mut self, record: Value) -> Result<()> {
+        if !self.started {
+            self.start(Some(&record))?;
+        }
+        let cells: Vec<String> = self
+            .headers
+            .iter()
+            .flatten()
+            .map(|h| markdown_escape(&cell_text(&record[h])))
+            .collect();
+        writeln!(self.writer, "| {} |", cells.join(" | "))?;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        if !self.started && self.headers.is_some() {
+            self.start(None)?;
+        }
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    fn columns(&mut self, columns: &[String]) -> Result<()> {
+        if self.headers.is_none() {
+            self.headers = Some(unique_columns(columns));
+        }
+        Ok(())
+    }
+}
+
+impl<W: Write> RecordWriter for HtmlWriter<W> {
+    fn write(&mut self, record: Value) -> Result<()> {
+        if !self.started {
+            self.start(Some(&record))?;
+        }
+        writeln!(self.writer, "    <tr>")?;
+        for h in self.headers.iter().flatten() {
+            let value = &record[h];
+            let class = if value.is_number() {
+                " class=\"number\""
+            } else {
+                ""
+            };
+            writeln!(
+                self.writer,
+                "      <td{}>{}</td>",
+                class,
+                html_escape(&cell_text(value))
+            )?;
+        }
+        writeln!(self.writer, "    </tr>")?;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        if !self.started {
+            self.start(None)?;
+        }
+        writeln!(self.writer, "  </tbody>\n</table>")?;
+        if self.standalone {
+            writeln!(self.writer, "</body>\n</html>")?;
+        }
+        self.writer.flush()?;
+        Ok(())
+    }
+    fn columns(&mut self, columns: &[String]) -> Result<()> {
+        if self.headers.is_none() {
+            self.headers = Some(unique_columns(columns));
+        }
+        Ok(())
+    }
+}
+
+impl<W: Write> RecordWriter for XmlWriter<W> {
+    fn write(&mut self, record: Value) -> Result<()> {
+        if !self.started {
+            self.start()?;
+        }
+        writeln!(self.writer, "  <{}>", self.row)?;
+        for (key, value) in record.as_object().into_iter().flatten() {
+            let text = xml_escape(&cell_text(value));
+            match &self.field {
+                // <field name="Kit Number">1</field>, 保留原始列名
+                Some(field) => writeln!(
+                    self.writer,
+                    "    <{} name=\"{}\">{}</{}>",
+                    field,
+                    xml_escape(key),
+                    text,
+                    field
+                )?,
+                None => {
+                    let name = xml_name(key);
+                    writeln!(self.writer, "    <{}>{}</{}>", name, text, name)?
+                }
+            }
+        }
+        writeln!(self.writer, "  </{}>", self.row)?;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        if !self.started {
+            self.start()?;
+        }
+        writeln!(self.writer, "</{}>", self.root)?;
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+impl<W: Write> MarkdownWriter<W> {
+    fn start(&mut self, record: Option<&Value>) -> Result<()> {
+        let headers = match (self.headers.take(), record) {
+            (Some(headers), _) => headers,
+            (None, Some(record)) => record_keys(record),
+            (None, None) => Vec::new(),
+        };
+        let escaped: Vec<String> = headers.iter().map(|h| markdown_escape(h)).collect();
+        writeln!(self.writer, "| {} |", escaped.join(" | "))?;
+        // 数字列右对齐, 没有记录时都左对齐
+        let align: Vec<&str> = headers
+            .iter()
+            .map(|h| match record {
+                Some(record) if record[h].is_number() => "---:",
+                _ => "---",
+            })
+            .collect();
+        writeln!(self.writer, "| {} |", align.join(" | "))?;
+        self.headers = Some(headers);
+        self.started = true;
+        Ok(())
+    }
+}
+
+impl<W: Write> HtmlWriter<W> {
+    // 既没有 columns() 也没有记录时只输出一个空的 tbody
+    fn start(&mut self, record: Option<&Value>) -> Result<()> {
+        if self.standalone {
+            self.writer.write_all(HTML_HEAD.as_bytes())?;
+        }
+        let headers = match (self.headers.take(), record) {
+            (Some(headers), _) => headers,
+            (None, Some(record)) => record_keys(record),
+            (None, None) => {
+                writeln!(self.writer, "<table>\n  <tbody>")?;
+                self.started = true;
+                return Ok(());
+            }
+        };
+        writeln!(self.writer, "<table>\n  <thead>\n    <tr>")?;
+        for h in &headers {
+            writeln!(self.writer, "      <th>{}</th>", html_escape(h))?;
+        }
+        writeln!(self.writer, "    </tr>\n  </thead>\n  <tbody>")?;
+        self.headers = Some(headers);
+        self.started = true;
+        Ok(())
+    }
+}
+
+impl<W: Write> XmlWriter<W> {
+    fn start(&mut self) -> Result<()> {
+        writeln!(self.writer, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>")?;
+        writeln!(self.writer, "<{}>", self.root)?;
+        self.started = true;
+        Ok(())
+    }
+}
+
+const HTML_HEAD: &str = r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<style>
+table { border-collapse: collapse; }
+th, td { border: 1px solid #ccc; padding: 4px 8px; }
+td.number { text-align: right; }
+</style>
+</head>
+<body>
+"#;
+
+// 重名的列与记录转成对象后一样只保留一个
+pub(crate) fn unique_columns(columns: &[String]) -> Vec<String> {
+    let mut unique: Vec<String> = Vec::with_capacity(columns.len());
+    for column in columns {
+        if !unique.contains(column) {
+            unique.push(column.clone());
+        }
+    }
+    unique
+}
+
+fn record_keys(record: &Value) -> Vec<String> {
+    record
+        .as_object()
+        .map(|map| map.keys().cloned().collect())
+        .unwrap_or_default()
+}
+
+fn cell_text(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        v => v.to_string(),
+    }
+}
+
+fn markdown_escape(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace('|', "\\|")
+        .replace("\r\n", "<br>")
+        .replace('\n', "<br>")
+}
+
+fn html_escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+fn xml_escape(s: &str) -> String {
+    html_escape(s).replace("&#39;", "&apos;")
+}
+
+// 列名不一定是合法的 XML 元素名: 非法字符替换成 `_`, 不能以数字等开头
+fn xml_name(name: &str) -> String {
+    let mut out: String = name
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || matches!(c, '_' | '-' | '.') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    if !out.starts_with(|c: char| c.is_alphabetic() || c == '_') {
+        out.insert(0, '_');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn records() -> Vec<Value> {
+        vec![
+            json!({"Name": "Buffon", "Kit Number": 77}),
+            json!({"Name": "Perin", "Kit Number": null}),
+        ]
+    }
+
+    #[test]
+    fn test_json_writer_matches_pretty() {
+        let mut writer = JsonWriter {
+            writer: Vec::new(),
+            count: 0,
+        };
+        for record in records() {
+            writer.write(record).unwrap();
+        }
+        writer.finish().unwrap();
+        let expected = serde_json::to_string_pretty(&records()).unwrap();
+        assert_eq!(String::from_utf8(writer.writer).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_csv_writer() {
+        let mut writer = CsvWriter {
+            writer: csv::Writer::from_writer(Vec::new()),
+            headers: None,
+        };
+        for record in records() {
+            writer.write(record).unwrap();
+        }
+        writer
+            .write(json!({"Name": "Chiellini, G.", "Kit Number": [3]}))
+            .unwrap();
+        writer.finish().unwrap();
+        let output = String::from_utf8(writer.writer.into_inner().unwrap()).unwrap();
+        assert_eq!(
+            output,
+            "Name,Kit Number\nBuffon,77\nPerin,\n\"Chiellini, G.\",[3]\n"
+        );
+
+        let mut writer = CsvWriter {
+            writer: csv::Writer::from_writer(Vec::new()),
+            headers: None,
+        };
+        let columns = ["Name", "Kit Number", "Name"].map(String::from);
+        writer.columns(&columns).unwrap();
+        writer.finish().unwrap();
+        let output = String::from_utf8(writer.writer.into_inner().unwrap()).unwrap();
+        assert_eq!(output, "Name,Kit Number\n");
+    }
+
+    #[test]
+    fn test_ndjson_writer() {
+        let mut writer = NdjsonWriter { writer: Vec::new() };
+        for record in records() {
+            writer.write(record).unwrap();
+        }
+        writer.finish().unwrap();
+        let output = String::from_utf8(writer.writer).unwrap();
+        assert_eq!(
+            output,
+            "{\"Name\":\"Buffon\",\"Kit Number\":77}\n{\"Name\":\"Perin\",\"Kit Number\":null}\n"
+        );
+    }
+
+    #[test]
+    fn test_yaml_and_toml_writer_round_trip() {
+        let mut writer = YamlWriter {
+            writer: Vec::new(),
+            count: 0,
+        };
+        for record in records() {
+            writer.write(record).unwrap();
+        }
+        writer.finish().unwrap();
+        let value: Vec<Value> = serde_yaml::from_slice(&writer.writer).unwrap();
+        assert_eq!(value, records());
+
+        let mut writer = TomlWriter { writer: Vec::new() };
+        for record in records() {
+            writer.write(record).unwrap();
+        }
+        writer.finish().unwrap();
+        let value: Value = toml::from_str(std::str::from_utf8(&writer.writer).unwrap()).unwrap();
+        assert_eq!(value["a"][1], json!({"Name": "Perin"}));
+    }
+
+    #[test]
+    fn test_markdown_writer() {
+        let mut writer = MarkdownWriter {
+            writer: Vec::new(),
+            headers: None,
+            started: false,
+        };
+        writer
+            .write(json!({"Name": "A|B", "Kit Number": 77}))
+            .unwrap();
+        writer.finish().unwrap();
+        assert_eq!(
+            String::from_utf8(writer.writer).unwrap(),
+            "| Name | Kit Number |\n| --- | ---: |\n| A\\|B | 77 |\n"
+        );
+    }
+
+    #[test]
+    fn test_html_writer_escapes() {
+        let mut writer = HtmlWriter {
+            writer: Vec::new(),
+            headers: None,
+            standalone: true,
+            started: false,
+        };
+        writer.write(json!({"Name": "<b>&</b>"})).unwrap();
+        writer.finish().unwrap();
+        let html = String::from_utf8(writer.writer).unwrap();
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("<td>&lt;b&gt;&amp;&lt;/b&gt;</td>"));
+        assert!(html.trim_end().ends_with("</html>"));
+    }
+
+    #[test]
+    fn test_markdown_and_html_without_records() {
+        let columns = ["Name", "Kit Number"].map(String::from);
+        let mut writer = MarkdownWriter {
+            writer: Vec::new(),
+            headers: None,
+            started: false,
+        };
+        writer.columns(&columns).unwrap();
+        writer.finish().unwrap();
+        assert_eq!(
+            String::from_utf8(writer.writer).unwrap(),
+            "| Name | Kit Number |\n| --- | --- |\n"
+        );
+
+        let mut writer = HtmlWriter {
+            writer: Vec::new(),
+            headers: None,
+            standalone: false,
+            started: false,
+        };
+        writer.columns(&columns).unwrap();
+        writer.finish().unwrap();
+        assert_eq!(
+            String::from_utf8(writer.writer).unwrap(),
+            "<table>\n  <thead>\n    <tr>\n      <th>Name</th>\n      <th>Kit Number</th>\n    \
+             </tr>\n  </thead>\n  <tbody>\n  </tbody>\n</table>\n"
+        );
+    }
+
+    #[test]
+    fn test_xml_writer_naming() {
+        let record = json!({"Kit Number": 1, "1st": "a<b"});
+        let mut writer = XmlWriter {
+            writer: Vec::new(),
+            root: "players".into(),
+            row: "player".into(),
+            field: None,
+            started: false,
+        };
+        writer.write(record.clone()).unwrap();
+        writer.finish().unwrap();
+        let xml = String::from_utf8(writer.writer).unwrap();
+        assert!(xml.contains("<players>\n  <player>\n    <Kit_Number>1</Kit_Number>"));
+        assert!(xml.contains("<_1st>a&lt;b</_1st>"));
+
+        let mut writer = XmlWriter {
+            writer: Vec::new(),
+            root: "rows".into(),
+            row: "row".into(),
+            field: Some("field".into()),
+            started: false,
+        };
+        writer.write(record).unwrap();
+        writer.finish().unwrap();
+        let xml = String::from_utf8(writer.writer).unwrap();
+        assert!(xml.contains("<field name=\"Kit Number\">1</field>"));
+    }
+}
@@ -0,0 +1,10 @@
+use std::io::Write;
+
+use tempfile::NamedTempFile;
+
+// 把测试数据写到临时文件, 文件在返回值 drop 时删除
+pub(crate) fn temp_file(content: impl AsRef<[u8]>) -> NamedTempFile {
+    let mut file = NamedTempFile::new().unwrap();
+    file.write_all(content.as_ref()).unwrap();
+    file
+}
@@ -15,7 +15,7 @@ trait TextVerify {
     fn verify(&self, reader: impl Read, sig: &[u8]) -> Result<bool>;
 }
 
-trait KeyLoader {
+pub(crate) trait KeyLoader {
     fn load(path: impl AsRef<Path>) -> Result<Self>
     where
         Self: Sized; // not str / [u8]
@@ -25,7 +25,7 @@ trait KeyGenerator {
     fn generate() -> Result<Vec<Vec<u8>>>;
 }
 
-struct Blake3 {
+pub(crate) struct Blake3 {
     key: [u8; 32],
 }
 
@@ -42,7 +42,7 @@ impl TextSign for Blake3 {
         let mut buf = Vec::new();
         reader.read_to_end(&mut buf)?;
 
-        let hash = blake3::keyed_hash(&self.key, &buf);
+        let hash = self.keyed_hash(&buf);
         Ok(hash.as_bytes().to_vec())
     }
 }
@@ -53,7 +53,7 @@ impl TextVerify for Blake3 {
         reader.read_to_end(&mut buf)?;
 
         // 注意这里需要先用hash绑定，再转换成bytes，否则&[u8]将会引用一个被free掉的value
-        let hash = blake3::keyed_hash(&self.key, &buf);
+        let hash = self.keyed_hash(&buf);
         Ok(hash.as_bytes() == sig)
     }
 }
@@ -85,10 +85,17 @@ impl Blake3 {
 
     // 关于什么时候使用 try_into()
     fn try_new(key: &[u8]) -> Result<Self> {
-        let key = &key[..32];
+        let key = key
+            .get(..32)
+            .ok_or_else(|| anyhow::anyhow!("Blake3 key must be at least 32 bytes"))?;
         let key = key.try_into()?;
         Ok(Self::new(key))
     }
+
+    // csv mask 也用它做确定性的假名化
+    pub(crate) fn keyed_hash(&self, data: &[u8]) -> blake3::Hash {
+        blake3::keyed_hash(&self.key, data)
+    }
 }
 
 impl KeyLoader for Blake3 {